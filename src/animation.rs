@@ -0,0 +1,231 @@
+use std::time::Duration;
+
+use ggez::graphics;
+use ggez::graphics::Color;
+use ggez::GameResult;
+
+use crate::engine::tet_points;
+use crate::event::{ClearKind, GameEvent, Subscriber};
+use crate::layout::Layout;
+use crate::{draw_square, BACKGROUND};
+
+// How long each effect runs for.
+const LINE_CLEAR_TIME: Duration = Duration::from_millis(300);
+const LOCK_FLASH_TIME: Duration = Duration::from_millis(150);
+const TRAIL_TIME: Duration = Duration::from_millis(200);
+const SHAKE_TIME: Duration = Duration::from_millis(350);
+const PERFECT_CLEAR_TIME: Duration = Duration::from_millis(1500);
+
+// How far the screen shakes, in cells, at the start of a shake.
+const SHAKE_DISTANCE: f32 = 0.4;
+
+// Which effects are turned on.
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationSettings {
+    pub line_clear: bool,
+    pub lock_flash: bool,
+    pub hard_drop_trail: bool,
+    pub screen_shake: bool,
+}
+
+impl Default for AnimationSettings {
+    fn default() -> AnimationSettings {
+        AnimationSettings {
+            line_clear: true,
+            lock_flash: true,
+            hard_drop_trail: true,
+            screen_shake: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Effect {
+    // Full rows flash white and then shrink away.
+    LineClear { rows: Vec<usize> },
+    // The squares of a piece that just locked flash white.
+    LockFlash { squares: Vec<(usize, usize)> },
+    // A streak behind each column of a hard dropped piece.  Each entry
+    // is the column, then the top and bottom rows of the streak.
+    Trail { streaks: Vec<(usize, usize, usize)> },
+    // The board shakes after a tetris.
+    Shake,
+    // Colours sweep over the empty board after a perfect clear.
+    PerfectClear,
+}
+
+#[derive(Debug)]
+struct Animation {
+    effect: Effect,
+    start: Duration,
+    length: Duration,
+}
+
+impl Animation {
+    // How far through the animation we are, from 0.0 at the start to
+    // 1.0 when it is done.
+    fn progress(&self, now: Duration) -> f32 {
+        let elapsed = now.checked_sub(self.start).unwrap_or_default();
+        (elapsed.as_secs_f32() / self.length.as_secs_f32()).min(1.0)
+    }
+}
+
+// The animator keeps track of the effects currently running.  Effects
+// are started from game events and run on the clock, not on the
+// gravity timer, so they look the same at every level.
+#[derive(Debug, Default)]
+pub struct Animator {
+    pub settings: AnimationSettings,
+    running: Vec<Animation>,
+}
+
+impl Animator {
+    pub fn new(settings: AnimationSettings) -> Animator {
+        Animator {
+            settings,
+            running: Vec::new(),
+        }
+    }
+
+    fn start(&mut self, effect: Effect, now: Duration, length: Duration) {
+        self.running.push(Animation {
+            effect,
+            start: now,
+            length,
+        });
+    }
+
+    // True while cleared rows are still being animated.  The game holds
+    // off removing the rows until this is done.
+    pub fn clearing_lines(&self, now: Duration) -> bool {
+        self.running.iter().any(|a| match a.effect {
+            Effect::LineClear { .. } => a.progress(now) < 1.0,
+            _ => false,
+        })
+    }
+
+    // How far to move the board this frame to shake it, in pixels.
+    pub fn shake_offset(&self, layout: &Layout, now: Duration) -> (f32, f32) {
+        let mut offset = (0.0, 0.0);
+        for a in self.running.iter() {
+            if let Effect::Shake = a.effect {
+                let t = a.progress(now);
+                let distance = SHAKE_DISTANCE * layout.cell * (1.0 - t);
+                offset.0 += distance * (t * 40.0).sin();
+                offset.1 += distance * (t * 30.0).cos() / 2.0;
+            }
+        }
+        offset
+    }
+
+    // Add the running effects to the mesh, and forget about any that
+    // have finished.
+    pub fn draw(
+        &mut self,
+        mb: &mut graphics::MeshBuilder,
+        layout: &Layout,
+        now: Duration,
+    ) -> GameResult {
+        self.running.retain(|a| a.progress(now) < 1.0);
+
+        for a in self.running.iter() {
+            let t = a.progress(now);
+            match &a.effect {
+                Effect::LineClear { rows } => {
+                    // Flash white for the first part, then fade back to
+                    // the base colour while shrinking away.  The row is
+                    // blanked first so the board's squares don't show
+                    // round the shrinking ones.
+                    let flash = (1.0 - t * 3.0).max(0.0);
+                    let color = Color::new(flash, 1.0, 0.5 + flash / 2.0, 1.0);
+                    let size = layout.cell * (1.0 - t);
+                    for y in rows.iter().filter(|&&y| layout.shows(y)) {
+                        for x in 2..layout.size.width + 2 {
+                            let (sx, sy) = layout.board_square(x, *y);
+                            let inset = (layout.cell - size) / 2.0;
+                            draw_square(mb, (sx, sy), layout.cell, BACKGROUND.into())?;
+                            draw_square(mb, (sx + inset, sy + inset), size, color)?;
+                        }
+                    }
+                }
+                Effect::LockFlash { squares } => {
+                    let color = Color::new(1.0, 1.0, 1.0, 1.0 - t);
+                    for (x, y) in squares.iter().filter(|&&(_, y)| layout.shows(y)) {
+                        draw_square(mb, layout.board_square(*x, *y), layout.cell, color)?;
+                    }
+                }
+                Effect::Trail { streaks } => {
+                    let color = Color::new(0.0, 1.0, 1.0, 0.5 * (1.0 - t));
+                    for (x, top, bottom) in streaks.iter() {
+                        for y in (*top).max(layout.first_row)..*bottom {
+                            draw_square(mb, layout.board_square(*x, y), layout.cell, color)?;
+                        }
+                    }
+                }
+                Effect::PerfectClear => {
+                    // Bands of colour rising up the board as it fades
+                    let fade = 1.0 - t;
+                    for y in layout.first_row..layout.size.rows() - 2 {
+                        for x in 2..layout.size.width + 2 {
+                            let phase = (x + y) as f32 * 0.5 + t * 12.0;
+                            let color = Color::new(
+                                0.5 + 0.5 * phase.sin(),
+                                0.5 + 0.5 * (phase + 2.1).sin(),
+                                0.5 + 0.5 * (phase + 4.2).sin(),
+                                0.6 * fade,
+                            );
+                            draw_square(mb, layout.board_square(x, y), layout.cell, color)?;
+                        }
+                    }
+                }
+                Effect::Shake => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Subscriber for Animator {
+    // Start any effects for this event.
+    fn notify(&mut self, event: &GameEvent, now: Duration) {
+        match event {
+            GameEvent::HardDrop { from, to } => {
+                if !self.settings.hard_drop_trail {
+                    return;
+                }
+                // For every column the piece covers, streak from the top
+                // of where it started to the top of where it landed.
+                let mut streaks: Vec<(usize, usize, usize)> = Vec::new();
+                for &(x, y) in tet_points(*to).iter() {
+                    let top = y - (to.y - from.y);
+                    match streaks.iter_mut().find(|s| s.0 == x) {
+                        Some(streak) => {
+                            streak.1 = streak.1.min(top);
+                            streak.2 = streak.2.min(y);
+                        }
+                        None => streaks.push((x, top, y)),
+                    }
+                }
+                self.start(Effect::Trail { streaks }, now, TRAIL_TIME);
+            }
+            GameEvent::Locked { piece } if self.settings.lock_flash => {
+                let squares = tet_points(*piece).to_vec();
+                self.start(Effect::LockFlash { squares }, now, LOCK_FLASH_TIME);
+            }
+            GameEvent::LinesCleared { kind, rows, .. } => {
+                if self.settings.line_clear {
+                    let rows = rows.clone();
+                    self.start(Effect::LineClear { rows }, now, LINE_CLEAR_TIME);
+                }
+                if self.settings.screen_shake && *kind == ClearKind::Tetris {
+                    self.start(Effect::Shake, now, SHAKE_TIME);
+                }
+            }
+            // Goes with the line clear animation
+            GameEvent::PerfectClear { .. } if self.settings.line_clear => {
+                self.start(Effect::PerfectClear, now, PERFECT_CLEAR_TIME);
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use ggez::audio;
+use ggez::audio::SoundSource;
+use ggez::Context;
+
+use crate::event::{ClearKind, Direction, GameEvent, Subscriber};
+
+// Each sound we can play, in response to game events.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Cue {
+    Move,
+    Rotate,
+    Lock,
+    HardDrop,
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    LevelUp,
+    Hold,
+    GameOver,
+}
+
+const CUES: [Cue; 11] = [
+    Cue::Move,
+    Cue::Rotate,
+    Cue::Lock,
+    Cue::HardDrop,
+    Cue::Single,
+    Cue::Double,
+    Cue::Triple,
+    Cue::Tetris,
+    Cue::LevelUp,
+    Cue::Hold,
+    Cue::GameOver,
+];
+
+const MUSIC_FILE: &str = "/music/theme.ogg";
+
+// How much each press of the volume keys changes the volume.
+const VOLUME_STEP: f32 = 0.1;
+
+impl Cue {
+    // Where the sound for this cue lives in the resources directory.
+    fn file(self) -> &'static str {
+        match self {
+            Cue::Move => "/sounds/move.ogg",
+            Cue::Rotate => "/sounds/rotate.ogg",
+            Cue::Lock => "/sounds/lock.ogg",
+            Cue::HardDrop => "/sounds/hard_drop.ogg",
+            Cue::Single => "/sounds/single.ogg",
+            Cue::Double => "/sounds/double.ogg",
+            Cue::Triple => "/sounds/triple.ogg",
+            Cue::Tetris => "/sounds/tetris.ogg",
+            Cue::LevelUp => "/sounds/level_up.ogg",
+            Cue::Hold => "/sounds/hold.ogg",
+            Cue::GameOver => "/sounds/game_over.ogg",
+        }
+    }
+
+    // The sound that goes with a game event, if any.
+    fn for_event(event: &GameEvent) -> Option<Cue> {
+        match event {
+            GameEvent::Moved { direction, .. } if *direction != Direction::Down => Some(Cue::Move),
+            GameEvent::Rotated { .. } => Some(Cue::Rotate),
+            GameEvent::HardDrop { .. } => Some(Cue::HardDrop),
+            GameEvent::Locked { .. } => Some(Cue::Lock),
+            GameEvent::LinesCleared { kind, .. } => match kind {
+                ClearKind::Single => Some(Cue::Single),
+                ClearKind::Double => Some(Cue::Double),
+                ClearKind::Triple => Some(Cue::Triple),
+                ClearKind::Tetris => Some(Cue::Tetris),
+            },
+            GameEvent::LevelUp { .. } => Some(Cue::LevelUp),
+            GameEvent::Hold { .. } => Some(Cue::Hold),
+            GameEvent::GameOver { .. } => Some(Cue::GameOver),
+            _ => None,
+        }
+    }
+}
+
+// All of the sound effects and the background music, along with the
+// volume settings.  A sound that fails to load is left out and simply
+// never plays, so a missing file never stops the game.  The game comes
+// without any, so missing files only get a line on the console between
+// them.
+pub struct Audio {
+    sounds: HashMap<Cue, audio::Source>,
+    music: Option<audio::Source>,
+    pub effects_volume: f32,
+    pub music_volume: f32,
+    muted: bool,
+}
+
+impl Audio {
+    pub fn new(ctx: &mut Context) -> Audio {
+        let mut sounds = HashMap::new();
+        let mut missing = Vec::new();
+        for cue in CUES.iter() {
+            match audio::Source::new(ctx, cue.file()) {
+                Ok(source) => {
+                    sounds.insert(*cue, source);
+                }
+                Err(_) => missing.push(cue.file()),
+            }
+        }
+
+        let music = match audio::Source::new(ctx, MUSIC_FILE) {
+            Ok(mut source) => {
+                source.set_repeat(true);
+                Some(source)
+            }
+            Err(_) => {
+                missing.push(MUSIC_FILE);
+                None
+            }
+        };
+        if !missing.is_empty() {
+            println!(
+                "{} of the {} sound files aren't in resources, see src/audio.rs for their names",
+                missing.len(),
+                CUES.len() + 1
+            );
+        }
+
+        let mut audio = Audio {
+            sounds,
+            music,
+            effects_volume: 0.8,
+            music_volume: 0.5,
+            muted: false,
+        };
+        audio.apply_volume();
+        audio
+    }
+
+    pub fn play(&mut self, cue: Cue) {
+        if self.muted {
+            return;
+        }
+        if let Some(source) = self.sounds.get_mut(&cue) {
+            // Detached so the same sound can overlap itself, for example
+            // when moving quickly across the board.
+            if let Err(e) = source.play_detached() {
+                println!("Unable to play {:?}: {}", cue, e);
+            }
+        }
+    }
+
+    pub fn start_music(&mut self) {
+        if let Some(music) = self.music.as_mut() {
+            if !music.playing() {
+                if let Err(e) = music.play() {
+                    println!("Unable to play music: {}", e);
+                }
+            }
+        }
+    }
+
+    pub fn stop_music(&mut self) {
+        if let Some(music) = self.music.as_mut() {
+            music.stop();
+        }
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.apply_volume();
+    }
+
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    // Turn everything up or down a step.
+    pub fn louder(&mut self) {
+        self.effects_volume = (self.effects_volume + VOLUME_STEP).min(1.0);
+        self.music_volume = (self.music_volume + VOLUME_STEP).min(1.0);
+        self.apply_volume();
+    }
+
+    pub fn quieter(&mut self) {
+        self.effects_volume = (self.effects_volume - VOLUME_STEP).max(0.0);
+        self.music_volume = (self.music_volume - VOLUME_STEP).max(0.0);
+        self.apply_volume();
+    }
+
+    // Push the current volume settings out to the sources.
+    pub fn apply_volume(&mut self) {
+        let (effects, music) = if self.muted {
+            (0.0, 0.0)
+        } else {
+            (self.effects_volume, self.music_volume)
+        };
+        for source in self.sounds.values_mut() {
+            source.set_volume(effects);
+        }
+        if let Some(source) = self.music.as_mut() {
+            source.set_volume(music);
+        }
+    }
+}
+
+impl Subscriber for Audio {
+    // Play the sound for a game event.
+    fn notify(&mut self, event: &GameEvent, _now: Duration) {
+        match event {
+            GameEvent::GameOver { .. } | GameEvent::Finished { .. } => self.stop_music(),
+            _ => (),
+        }
+        if let Some(cue) = Cue::for_event(event) {
+            self.play(cue);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Piece, TileType};
+
+// The board as a bit mask for each row, with one bit set for every
+// square that a piece can't move into, bit x for column x.  Checking
+// whether a piece fits is then a few ANDs of its shape against the
+// rows, and a row is full when its mask has every playfield column
+// set.  What is in each square, for drawing, is kept alongside in the
+// tiles.
+//
+// The masks are big enough for the largest playfield, but the tiles
+// are only as big as the board in use, so the boxes beside the
+// playfield stay small.
+
+// How big the playfield can be.
+pub const MIN_WIDTH: usize = 4;
+pub const MAX_WIDTH: usize = 20;
+pub const MIN_HEIGHT: usize = 10;
+pub const MAX_HEIGHT: usize = 40;
+pub const MAX_HIDDEN: usize = 20;
+
+pub const MAX_COLUMNS: usize = MAX_WIDTH + 4;
+pub const MAX_ROWS: usize = MAX_HIDDEN + MAX_HEIGHT + 2;
+
+// The size of the playfield: its columns, the rows that are shown and
+// the rows hidden above them.  By default that is the guideline's ten
+// by twenty, with another twenty rows above for pieces to come in and
+// stack up into out of sight.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Size {
+    pub width: usize,
+    pub height: usize,
+    pub hidden: usize,
+}
+
+impl Default for Size {
+    fn default() -> Size {
+        Size {
+            width: 10,
+            height: 20,
+            hidden: 20,
+        }
+    }
+}
+
+impl Size {
+    // The board array around the playfield.  Column 0 and the last
+    // column give pieces room for the empty columns of their boxes,
+    // and the walls are inside those at column 1 and just right of the
+    // playfield.  Below the playfield is the floor, then a spare row.
+    pub fn columns(self) -> usize {
+        self.width + 4
+    }
+
+    pub fn rows(self) -> usize {
+        self.hidden + self.height + 2
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "SavedBoard", into = "SavedBoard")]
+pub struct Board {
+    width: usize,
+    height: usize,
+    rows: [u32; MAX_ROWS],
+    // Row by row, width squares to a row.
+    tiles: Vec<TileType>,
+}
+
+// A board as it is saved, with the tiles of only the rows and columns
+// in use.  The masks are worked out again from the tiles.
+#[derive(Serialize, Deserialize)]
+struct SavedBoard {
+    width: usize,
+    height: usize,
+    tiles: Vec<Vec<TileType>>,
+}
+
+impl From<Board> for SavedBoard {
+    fn from(board: Board) -> SavedBoard {
+        SavedBoard {
+            width: board.width,
+            height: board.height,
+            tiles: board
+                .tiles
+                .chunks(board.width)
+                .map(|row| row.to_vec())
+                .collect(),
+        }
+    }
+}
+
+impl From<SavedBoard> for Board {
+    fn from(saved: SavedBoard) -> Board {
+        let mut board = Board::new(saved.width, saved.height);
+        for (y, row) in saved.tiles.iter().take(board.height).enumerate() {
+            for (x, &tile) in row.iter().take(board.width).enumerate() {
+                board.set(x, y, tile);
+            }
+        }
+        board
+    }
+}
+
+// Pieces and walls stop other pieces, the piece in play and blank
+// squares don't.
+fn is_solid(tile: TileType) -> bool {
+    tile.is_filled() || tile == TileType::Border
+}
+
+impl Board {
+    // A board this many squares across and down with nothing on it,
+    // not even the walls.
+    pub fn new(width: usize, height: usize) -> Board {
+        let width = width.min(MAX_COLUMNS);
+        let height = height.min(MAX_ROWS);
+        Board {
+            width,
+            height,
+            rows: [0; MAX_ROWS],
+            tiles: vec![TileType::Blank; width * height],
+        }
+    }
+
+    // An empty playfield of the given size, with its walls and floor.
+    pub fn playfield(size: Size) -> Board {
+        let mut board = Board::new(size.columns(), size.rows());
+        let right = board.width - 2;
+        let floor = board.height - 2;
+        for y in 0..board.height - 1 {
+            board.set(1, y, TileType::Border);
+            board.set(right, y, TileType::Border);
+        }
+        for x in 1..right {
+            board.set(x, floor, TileType::Border);
+        }
+        board
+    }
+
+    // The size of the board array, walls and all.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Off the board reads as a wall, and setting it does nothing.
+    pub fn get(&self, x: usize, y: usize) -> TileType {
+        if x >= self.width || y >= self.height {
+            return TileType::Border;
+        }
+        self.tiles[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, tile: TileType) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        self.tiles[y * self.width + x] = tile;
+        if is_solid(tile) {
+            self.rows[y] |= 1 << x;
+        } else {
+            self.rows[y] &= !(1 << x);
+        }
+    }
+
+    // Is there room for the piece where it is?  Anything off the edge
+    // of the board doesn't fit.
+    pub fn fits(&self, piece: Piece) -> bool {
+        for (dy, &mask) in piece.rows().iter().enumerate() {
+            if mask == 0 {
+                continue;
+            }
+            let y = piece.y + dy;
+            if y >= self.height {
+                return false;
+            }
+            let row = u32::from(mask) << piece.x;
+            if row >> self.width != 0 || row & self.rows[y] != 0 {
+                return false;
+            }
+        }
+        true
+    }
+
+    // The mask of the squares taken in a row, walls included.
+    pub fn row(&self, y: usize) -> u32 {
+        self.rows[y]
+    }
+
+    // The playfield columns, from 2 to just inside the right wall.
+    pub fn full_row(&self) -> u32 {
+        ((1 << (self.width - 4)) - 1) << 2
+    }
+
+    // Every playfield square in the row is taken.
+    pub fn is_full(&self, y: usize) -> bool {
+        self.rows[y] & self.full_row() == self.full_row()
+    }
+
+    // No playfield square in the row is taken.
+    pub fn is_empty(&self, y: usize) -> bool {
+        self.rows[y] & self.full_row() == 0
+    }
+
+    // Copy one row over another, walls and all.
+    pub fn copy_row(&mut self, from: usize, to: usize) {
+        self.rows[to] = self.rows[from];
+        let width = self.width;
+        self.tiles
+            .copy_within(from * width..(from + 1) * width, to * width);
+    }
+
+    // Blank the playfield squares of a row, leaving the walls.
+    pub fn clear_row(&mut self, y: usize) {
+        for x in 2..self.width - 2 {
+            self.set(x, y, TileType::Blank);
+        }
+    }
+}
@@ -0,0 +1,611 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::MAX_ROWS;
+use crate::engine::{rotated, validate_move, Action, Board, BoardState, Game, Piece};
+use crate::event::{GameEvent, Subscriber};
+use crate::pieces::{PieceSet, BOX};
+
+// A computer player.  For the piece in play, and the one it would get
+// by holding, it finds every place the piece can be moved to and
+// dropped, works out what the board would look like afterwards, and
+// scores that with a weighted sum of features of the board.  The
+// piece is then walked to the best place one action at a time, through
+// Game::apply like any other player.
+//
+// The features are Pierre Dellacherie's, as weighted by El-Tetris:
+// how high the piece lands, the squares of it that go with the lines
+// it clears, how often the squares change between empty and filled
+// along the rows and down the columns, the holes and the wells.  The
+// plainer aggregate height, bumpiness and lines cleared are there too,
+// weighted at nothing unless bot.json in the data directory says
+// otherwise.
+
+// What each feature of a board is worth.  Any left out of bot.json
+// keep their defaults.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct Weights {
+    pub landing_height: f64,
+    pub eroded_cells: f64,
+    pub row_transitions: f64,
+    pub column_transitions: f64,
+    pub holes: f64,
+    pub wells: f64,
+    pub aggregate_height: f64,
+    pub bumpiness: f64,
+    pub lines: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights {
+            landing_height: -4.500158825082766,
+            eroded_cells: 3.4181268101392694,
+            row_transitions: -3.2178882868487753,
+            column_transitions: -9.348695305445199,
+            holes: -7.899265427351652,
+            wells: -3.3855972247263626,
+            aggregate_height: 0.0,
+            bumpiness: 0.0,
+            lines: 0.0,
+        }
+    }
+}
+
+impl Weights {
+    pub fn load(path: &Path) -> Option<Weights> {
+        let text = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&text) {
+            Ok(weights) => Some(weights),
+            Err(e) => {
+                println!("Unable to read {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn score(&self, features: &Features) -> f64 {
+        self.landing_height * features.landing_height
+            + self.eroded_cells * features.eroded_cells
+            + self.row_transitions * features.row_transitions
+            + self.column_transitions * features.column_transitions
+            + self.holes * features.holes
+            + self.wells * features.wells
+            + self.aggregate_height * features.aggregate_height
+            + self.bumpiness * features.bumpiness
+            + self.lines * features.lines
+    }
+}
+
+// The features of a board once a piece has been dropped on it and any
+// lines it made are cleared.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Features {
+    // Rows up from the floor to the middle of the piece.
+    pub landing_height: f64,
+    // Lines cleared times the squares of the piece in them.
+    pub eroded_cells: f64,
+    pub row_transitions: f64,
+    pub column_transitions: f64,
+    // Empty squares with something above them.
+    pub holes: f64,
+    // Each well counts 1 + 2 + ... for its depth.
+    pub wells: f64,
+    pub aggregate_height: f64,
+    // The differences in height between neighbouring columns.
+    pub bumpiness: f64,
+    pub lines: f64,
+}
+
+// Somewhere the piece can be dropped, and the actions that get it
+// there, ending with the hard drop.
+#[derive(Debug, Clone)]
+pub struct Placement {
+    pub piece: Piece,
+    pub actions: Vec<Action>,
+}
+
+// The squares a piece covers, as the first row with any in it and the
+// row masks from there down, to tell places apart.  Some pieces look
+// the same in more than one rotation, and those only count once.
+type Footprint = (usize, [u32; BOX]);
+
+fn footprint(piece: Piece) -> Footprint {
+    let rows = piece.rows();
+    let top = rows.iter().position(|&mask| mask != 0).unwrap_or(0);
+    let mut masks = [0; BOX];
+    for (i, &mask) in rows[top..].iter().enumerate() {
+        masks[i] = u32::from(mask) << piece.x;
+    }
+    (piece.y + top, masks)
+}
+
+// The last row with any of the footprint in it.
+fn bottom((top, masks): Footprint) -> usize {
+    top + masks.iter().rposition(|&mask| mask != 0).unwrap_or(0)
+}
+
+// Where the piece ends up if it is hard dropped.
+fn dropped(board: &Board, mut piece: Piece) -> Piece {
+    loop {
+        let below = Piece {
+            y: piece.y + 1,
+            ..piece
+        };
+        if !validate_move(board, below) {
+            return piece;
+        }
+        piece = below;
+    }
+}
+
+// The piece after one action, if it can be done without locking.
+fn moved(board: &Board, piece: Piece, action: Action, pieces: &PieceSet) -> Option<Piece> {
+    let to = match action {
+        Action::MoveLeft if piece.x > 0 => Piece {
+            x: piece.x - 1,
+            ..piece
+        },
+        Action::MoveRight => Piece {
+            x: piece.x + 1,
+            ..piece
+        },
+        Action::SoftDrop => Piece {
+            y: piece.y + 1,
+            ..piece
+        },
+        // Kicks that lift the piece are left out.  Gravity pulling it
+        // back down holds off the lock, so turning it up and over again
+        // could keep it off the stack forever.
+        Action::Rotate => {
+            return rotated(board, piece, pieces.kicks(piece.tet_type))
+                .map(|(to, _)| to)
+                .filter(|to| to.y >= piece.y)
+        }
+        _ => return None,
+    };
+    if validate_move(board, to) {
+        Some(to)
+    } else {
+        None
+    }
+}
+
+// Every place the piece can be dropped from where it is now.  This is
+// a breadth first search over moves, turns and soft drops, so each
+// place comes with the fewest actions that reach it, and places that
+// need the piece tucked under an overhang or spun in are found too.
+pub fn placements(board: &Board, start: Piece, pieces: &PieceSet) -> Vec<Placement> {
+    let mut found = Vec::new();
+    let mut landed = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    seen.insert((start.x, start.y, start.rotation));
+    queue.push_back((start, Vec::new()));
+    while let Some((piece, actions)) = queue.pop_front() {
+        let landing = dropped(board, piece);
+        if landed.insert(footprint(landing)) {
+            let mut actions = actions.clone();
+            actions.push(Action::HardDrop);
+            found.push(Placement {
+                piece: landing,
+                actions,
+            });
+        }
+        for &action in &[
+            Action::Rotate,
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::SoftDrop,
+        ] {
+            if let Some(to) = moved(board, piece, action, pieces) {
+                if seen.insert((to.x, to.y, to.rotation)) {
+                    let mut actions = actions.clone();
+                    actions.push(action);
+                    queue.push_back((to, actions));
+                }
+            }
+        }
+    }
+    found
+}
+
+// Drop the piece where it is onto a copy of the board's rows, clear
+// any lines it makes and measure what is left.  Everything is worked
+// out a row at a time on the masks, with the walls set in every row so
+// that an empty square against one is a change.
+pub fn measure(board: &Board, piece: Piece) -> Features {
+    let floor = board.height() - 2;
+    let right = board.width() - 2;
+    let field = board.full_row();
+    let walls = (1 << 1) | (1 << right);
+    let (top, shape) = footprint(piece);
+    let mut rows = [0; MAX_ROWS];
+    for (y, row) in rows[..floor].iter_mut().enumerate() {
+        *row = board.row(y);
+    }
+    for (dy, &mask) in shape.iter().enumerate().filter(|&(_, &mask)| mask != 0) {
+        rows[top + dy] |= mask;
+    }
+
+    // Take out the full rows, moving the rest down, and count the
+    // squares of the piece that went with them.
+    let mut lines = 0;
+    let mut eroded = 0;
+    let mut kept = floor;
+    for y in (0..floor).rev() {
+        if rows[y] & field == field {
+            lines += 1;
+            if y >= top && y < top + BOX {
+                eroded += shape[y - top].count_ones();
+            }
+        } else {
+            kept -= 1;
+            rows[kept] = rows[y];
+        }
+    }
+    for row in rows[..kept].iter_mut() {
+        *row = walls;
+    }
+
+    let bottom = bottom((top, shape));
+    let mut features = Features {
+        landing_height: (floor - 1 - bottom) as f64 + (bottom - top) as f64 / 2.0,
+        eroded_cells: f64::from(lines * eroded),
+        lines: f64::from(lines),
+        ..Features::default()
+    };
+
+    // Down the rows from the top, keeping the row above, the columns
+    // with something in them further up, and for each column how tall
+    // it is and how deep the well it's in has got.
+    let edges = field | (1 << right);
+    let mut above = 0;
+    let mut covered = 0;
+    let mut heights = [0; 32];
+    let mut depths = [0; 32];
+    for (y, &row) in rows[..floor].iter().enumerate() {
+        features.row_transitions += f64::from(((row ^ (row << 1)) & edges).count_ones());
+        features.column_transitions += f64::from(((row ^ above) & field).count_ones());
+        features.holes += f64::from((!row & covered & field).count_ones());
+        let wells = !row & (row << 1) & (row >> 1) & field;
+        for x in 2..right {
+            if wells & (1 << x) != 0 {
+                depths[x] += 1;
+                features.wells += f64::from(depths[x]);
+            } else {
+                depths[x] = 0;
+            }
+            if row & !covered & (1 << x) != 0 {
+                heights[x] = floor - y;
+            }
+        }
+        covered |= row;
+        above = row;
+    }
+    // The floor below counts as filled
+    features.column_transitions += f64::from((!above & field).count_ones());
+
+    let heights = &heights[2..right];
+    features.aggregate_height = heights.iter().sum::<usize>() as f64;
+    features.bumpiness = heights
+        .windows(2)
+        .map(|pair| (pair[0] as f64 - pair[1] as f64).abs())
+        .sum();
+    features
+}
+
+// Where a piece is, to tell whether it has moved.
+fn position(piece: Piece) -> (usize, usize, u8) {
+    (piece.x, piece.y, piece.rotation)
+}
+
+// The actions that take the piece from where it is to the target,
+// dropping it there, if it can still get there.
+fn route(board: &Board, piece: Piece, pieces: &PieceSet, target: Footprint) -> Option<Vec<Action>> {
+    placements(board, piece, pieces)
+        .into_iter()
+        .find(|placement| footprint(placement.piece) == target)
+        .map(|placement| placement.actions)
+}
+
+// Plays a game with the given weights, making a move every so often.
+pub struct Bot {
+    weights: Weights,
+    // The time between actions, and how long it has waited so far for
+    // the next one.
+    delay: Duration,
+    waited: Duration,
+    // The place the piece in play is being taken to, the actions left
+    // to get it there, and where the piece should be for the next of
+    // them.
+    target: Option<Footprint>,
+    path: VecDeque<Action>,
+    at: Option<(usize, usize, u8)>,
+}
+
+impl Bot {
+    pub fn new(weights: Weights, delay: Duration) -> Bot {
+        Bot {
+            weights,
+            delay: delay.max(Duration::from_millis(1)),
+            waited: Duration::from_secs(0),
+            target: None,
+            path: VecDeque::new(),
+            at: None,
+        }
+    }
+
+    // Make as many moves as there has been time for.  The bot only
+    // plays while a piece is in play, and doesn't save up time while
+    // lines are cleared or the game is paused.
+    pub fn play(&mut self, game: &mut Game, dt: Duration) {
+        if game.board_state != BoardState::Moving {
+            self.waited = Duration::from_secs(0);
+            return;
+        }
+        self.waited += dt;
+        while self.waited >= self.delay && game.board_state == BoardState::Moving {
+            self.waited -= self.delay;
+            self.step(game);
+        }
+    }
+
+    // One action towards the best place for the piece, picking the
+    // place and the way there first if there isn't one yet.
+    pub fn step(&mut self, game: &mut Game) {
+        let target = match self.target {
+            Some(target) => target,
+            // The piece holding brings in starts somewhere else, so
+            // its place is picked again once it is in play.
+            None => match self.choose(game) {
+                Some((true, _)) => {
+                    game.apply(Action::Hold);
+                    return;
+                }
+                Some((false, placement)) => {
+                    let target = footprint(placement.piece);
+                    self.target = Some(target);
+                    self.path = placement.actions.into();
+                    self.at = Some(position(game.piece));
+                    target
+                }
+                None => return,
+            },
+        };
+        // Gravity may have moved the piece since, or the last action
+        // didn't work, so find the way there again from where it is.
+        if self.at != Some(position(game.piece)) {
+            match route(&game.board, game.piece, &game.pieces, target) {
+                Some(actions) => self.path = actions.into(),
+                None => return self.target = None,
+            }
+        }
+        let action = match self.path.pop_front() {
+            Some(action) => action,
+            None => return self.target = None,
+        };
+        let done = game.apply(action);
+        if action == Action::HardDrop {
+            self.target = None;
+        } else if done {
+            self.at = Some(position(game.piece));
+        } else {
+            self.at = None;
+        }
+    }
+
+    // The best place for the piece in play or, if holding does
+    // better, the piece that holding brings in.  Says whether to hold,
+    // and the place with the way there.
+    fn choose(&self, game: &Game) -> Option<(bool, Placement)> {
+        let here = self.best(game, game.piece);
+        let held = if game.can_hold() {
+            let tet_type = game.held.unwrap_or(game.next_piece.tet_type);
+            self.best(game, game.pieces.spawn(tet_type))
+        } else {
+            None
+        };
+        match (here, held) {
+            (Some(here), Some(held)) if held.0 > here.0 => Some((true, held.1)),
+            (None, Some(held)) => Some((true, held.1)),
+            (Some(here), _) => Some((false, here.1)),
+            (None, None) => None,
+        }
+    }
+
+    // The best place for a piece starting from where it is, with its
+    // score.  Places entirely in the hidden rows would end the game,
+    // so they are left out.  The first of any equally good places is
+    // kept.
+    fn best(&self, game: &Game, start: Piece) -> Option<(f64, Placement)> {
+        if !validate_move(&game.board, start) {
+            return None;
+        }
+        let hidden = game.rules.size.hidden;
+        let mut best: Option<(f64, Placement)> = None;
+        for placement in placements(&game.board, start, &game.pieces) {
+            if bottom(footprint(placement.piece)) < hidden {
+                continue;
+            }
+            let score = self.weights.score(&measure(&game.board, placement.piece));
+            match best {
+                Some((most, _)) if score <= most => (),
+                _ => best = Some((score, placement)),
+            }
+        }
+        best
+    }
+}
+
+// A new piece, or the board moving under the piece, means the place
+// picked for it no longer applies.
+impl Subscriber for Bot {
+    fn notify(&mut self, event: &GameEvent, _now: Duration) {
+        match event {
+            GameEvent::PieceSpawned { .. }
+            | GameEvent::Hold { .. }
+            | GameEvent::Undone { .. }
+            | GameEvent::GarbageAdded { .. } => self.target = None,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Size;
+    use crate::diagram::Diagram;
+    use crate::engine::{moved_onto, tet_points, Rules};
+    use crate::scoring::ScoreTable;
+    use crate::spin::Spin;
+
+    fn diagram(text: &str) -> Diagram {
+        Diagram::parse(text, Size::default(), &PieceSet::default()).unwrap()
+    }
+
+    // Where a placement leaves the piece's squares.
+    fn squares(piece: Piece) -> Vec<(usize, usize)> {
+        let mut squares = tet_points(piece);
+        squares.sort_unstable();
+        squares
+    }
+
+    // The placement that leaves the piece on these squares, if any.
+    fn placed_on(found: &[Placement], mut wanted: Vec<(usize, usize)>) -> Option<&Placement> {
+        wanted.sort_unstable();
+        found.iter().find(|p| squares(p.piece) == wanted)
+    }
+
+    // Play the actions in a game set up from the diagram.
+    fn play(diagram: Diagram, actions: &[Action]) -> Game {
+        let piece = diagram.piece.unwrap();
+        let mut game = Game::new(
+            1,
+            Rules::default(),
+            Box::new(ScoreTable::default()),
+            PieceSet::default(),
+        );
+        game.set_up(diagram.board, piece.tet_type, None, &[]);
+        game.piece = piece;
+        for &action in actions {
+            assert!(game.apply(action), "{:?} did nothing", action);
+        }
+        game
+    }
+
+    #[test]
+    fn t_spin_slot_needs_a_kick() {
+        // The T can't drop straight into the slot past the square over
+        // its right corner, only turn into it at the bottom.
+        let diagram = diagram(
+            "piece: T\n\
+             ..########\n\
+             ...#######\n\
+             #.########\n",
+        );
+        let start = diagram.piece.unwrap();
+        let slot = vec![(2, 38), (3, 38), (4, 38), (3, 39)];
+        let straight = Piece {
+            rotation: 2,
+            ..start
+        };
+        assert!(!(0..8).any(|x| squares(dropped(&diagram.board, Piece { x, ..straight })) == slot));
+
+        let found = placements(&diagram.board, start, &PieceSet::default());
+        let placement = placed_on(&found, slot).expect("no way into the slot");
+        assert_eq!(placement.actions.last(), Some(&Action::HardDrop));
+        let mut game = play(diagram, &placement.actions);
+        let spun = game.take_events().iter().find_map(|event| match event {
+            GameEvent::Spun { spin, lines, .. } => Some((*spin, *lines)),
+            _ => None,
+        });
+        assert_eq!(spun, Some((Spin::Full, 2)));
+    }
+
+    #[test]
+    fn tuck_under_an_overhang() {
+        // The O goes down the gap on the left and along the bottom to
+        // the far wall.
+        let diagram = diagram(
+            "piece: O\n\
+             ...#######\n\
+             ..........\n\
+             ..........\n",
+        );
+        let found = placements(&diagram.board, diagram.piece.unwrap(), &PieceSet::default());
+        let tucked = vec![(10, 38), (11, 38), (10, 39), (11, 39)];
+        let placement = placed_on(&found, tucked.clone()).expect("no tuck");
+        assert!(placement.actions.contains(&Action::SoftDrop));
+        let at = placement
+            .actions
+            .iter()
+            .rposition(|&a| a == Action::SoftDrop);
+        assert!(placement.actions[at.unwrap()..].contains(&Action::MoveRight));
+
+        let game = play(diagram, &placement.actions);
+        for (x, y) in tucked {
+            assert!(game.board.get(x, y).is_filled());
+        }
+    }
+
+    #[test]
+    fn features() {
+        // The I clears the bottom row, leaving
+        //
+        //     ...#......
+        //     ##.#......
+        //     #.##......
+        let diagram = diagram(
+            "piece: I\n\
+             ...i......\n\
+             ##.i......\n\
+             #.#i......\n\
+             ###i######\n",
+        );
+        let features = measure(&diagram.board, diagram.piece.unwrap());
+        let expected = Features {
+            landing_height: 1.5,
+            eroded_cells: 1.0,
+            // Two for every empty row between the walls
+            row_transitions: 37.0 * 2.0 + 12.0,
+            column_transitions: 12.0,
+            holes: 1.0,
+            wells: 2.0,
+            aggregate_height: 8.0,
+            bumpiness: 6.0,
+            lines: 1.0,
+        };
+        assert_eq!(features, expected);
+    }
+
+    #[test]
+    fn route_to_a_target() {
+        let diagram = diagram(
+            "piece: O\n\
+             ...#######\n\
+             ..........\n\
+             ..........\n",
+        );
+        let pieces = PieceSet::default();
+        let start = diagram.piece.unwrap();
+        let tucked = moved_onto(start, &[(10, 38), (11, 38), (10, 39), (11, 39)]).unwrap();
+        let actions = route(&diagram.board, start, &pieces, footprint(tucked)).unwrap();
+        assert_eq!(actions.last(), Some(&Action::HardDrop));
+        let game = play(diagram.clone(), &actions);
+        assert!(game.board.get(10, 39).is_filled());
+
+        // A place in the stack can't be reached
+        let buried = Piece {
+            y: tucked.y - 1,
+            ..tucked
+        };
+        assert!(route(&diagram.board, start, &pieces, footprint(buried)).is_none());
+    }
+}
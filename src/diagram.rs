@@ -0,0 +1,251 @@
+use crate::board::Size;
+use crate::engine::{moved_onto, tet_points, Board, Game, Piece, TileType};
+use crate::pieces::{PieceSet, PieceType};
+
+// Boards written out as text, to be read back in.  The debug dump
+// prints them, the editor copies and pastes them, and they are easy to
+// write by hand:
+//
+//     piece: T
+//     hold: I
+//     queue: S Z O
+//     ....t.....
+//     ...ttt....
+//     #..##LL###
+//     ######.###
+//
+// Each line below the header is a row of the playfield, from the top
+// down, with `.` for an empty square, `#` for the stack or garbage and
+// a piece's name for a square in that piece's colour.  The piece in
+// play can be drawn where it is in lower case, otherwise it comes in
+// at the top.  The rows go at the bottom of the board, so only those
+// with anything in need be given, and each is as wide as the board.
+// Only pieces named with one capital letter can be drawn; the squares
+// of any others are written as `#`.
+//
+// The header lines are each optional: the piece in play, the held
+// piece and the pieces to come, by name.
+
+#[derive(Debug, Clone)]
+pub struct Diagram {
+    pub board: Board,
+    pub piece: Option<Piece>,
+    pub held: Option<PieceType>,
+    pub queue: Vec<PieceType>,
+}
+
+// The letter a piece is drawn with, if it has one.
+fn letter(pieces: &PieceSet, tet_type: PieceType) -> Option<char> {
+    let mut chars = pieces.name(tet_type).chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_uppercase() => Some(c),
+        _ => None,
+    }
+}
+
+fn find(pieces: &PieceSet, name: &str) -> Result<PieceType, String> {
+    pieces
+        .find(name)
+        .ok_or_else(|| format!("there is no {} piece in {}", name, pieces.name))
+}
+
+impl Diagram {
+    // The position in a game, with the piece in play where it is.
+    pub fn of_game(game: &Game) -> Diagram {
+        Diagram {
+            board: game.board.clone(),
+            piece: Some(game.piece),
+            held: game.held,
+            queue: game.piece_queue.coming(),
+        }
+    }
+
+    pub fn parse(text: &str, size: Size, pieces: &PieceSet) -> Result<Diagram, String> {
+        let mut board = Board::playfield(size);
+        let mut piece_type = None;
+        let mut held = None;
+        let mut queue = Vec::new();
+        let mut rows = Vec::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match line.split_once(':') {
+                Some((name, value)) => {
+                    let value = value.trim();
+                    match name.trim() {
+                        "piece" => piece_type = Some(find(pieces, value)?),
+                        "hold" if value.is_empty() || value == "none" => held = None,
+                        "hold" => held = Some(find(pieces, value)?),
+                        "queue" => {
+                            queue = value
+                                .split_whitespace()
+                                .map(|name| find(pieces, name))
+                                .collect::<Result<_, _>>()?
+                        }
+                        name => return Err(format!("\"{}\" isn't a header we know", name)),
+                    }
+                }
+                None => rows.push(line),
+            }
+        }
+
+        let floor = board.height() - 2;
+        if rows.len() > floor {
+            return Err(format!(
+                "there are {} rows, the board has {}",
+                rows.len(),
+                floor
+            ));
+        }
+        // The squares of the piece in play, if it is drawn, and which
+        // piece it is.
+        let mut squares = Vec::new();
+        let mut drawn = None;
+        for (i, row) in rows.iter().enumerate() {
+            let y = floor - rows.len() + i;
+            if row.chars().count() != size.width {
+                return Err(format!("\"{}\" isn't {} squares wide", row, size.width));
+            }
+            for (x, c) in row.chars().enumerate() {
+                let tile = match c {
+                    '.' => TileType::Blank,
+                    '#' => TileType::Base,
+                    c if c.is_ascii_uppercase() => TileType::Piece(find(pieces, &c.to_string())?),
+                    c if c.is_ascii_lowercase() => {
+                        let tet_type = find(pieces, &c.to_ascii_uppercase().to_string())?;
+                        if matches!(drawn, Some(drawn) if drawn != tet_type) {
+                            return Err("there is more than one piece in play".to_string());
+                        }
+                        drawn = Some(tet_type);
+                        squares.push((x + 2, y));
+                        TileType::Blank
+                    }
+                    c => return Err(format!("'{}' isn't a square", c)),
+                };
+                board.set(x + 2, y, tile);
+            }
+        }
+
+        let pieces = pieces.clone().sized(size);
+        let piece = match (piece_type, drawn) {
+            (Some(named), Some(drawn)) if named != drawn => {
+                return Err(format!(
+                    "the piece is {} but {} is drawn",
+                    pieces.name(named),
+                    pieces.name(drawn)
+                ))
+            }
+            (_, Some(tet_type)) => {
+                let piece = (0..4)
+                    .map(|rotation| Piece {
+                        rotation,
+                        ..pieces.spawn(tet_type)
+                    })
+                    .find_map(|piece| moved_onto(piece, &squares));
+                Some(piece.ok_or_else(|| {
+                    format!(
+                        "the {} in play isn't the right shape",
+                        pieces.name(tet_type)
+                    )
+                })?)
+            }
+            (Some(tet_type), None) => Some(pieces.spawn(tet_type)),
+            (None, None) => None,
+        };
+        Ok(Diagram {
+            board,
+            piece,
+            held,
+            queue,
+        })
+    }
+
+    pub fn to_text(&self, pieces: &PieceSet) -> String {
+        let mut text = String::new();
+        if let Some(piece) = self.piece {
+            text.push_str(&format!("piece: {}\n", pieces.name(piece.tet_type)));
+        }
+        if let Some(held) = self.held {
+            text.push_str(&format!("hold: {}\n", pieces.name(held)));
+        }
+        if !self.queue.is_empty() {
+            let names: Vec<&str> = self.queue.iter().map(|&t| pieces.name(t)).collect();
+            text.push_str(&format!("queue: {}\n", names.join(" ")));
+        }
+
+        // Draw the piece in play on a copy of the board, if it has a
+        // letter to draw it with.
+        let mut board = self.board.clone();
+        let mut in_play = None;
+        if let Some(piece) = self.piece {
+            if let Some(c) = letter(pieces, piece.tet_type) {
+                in_play = Some(c.to_ascii_lowercase());
+                for (x, y) in tet_points(piece) {
+                    board.set(x, y, TileType::Tet);
+                }
+            }
+        }
+        let floor = board.height() - 2;
+        let columns = 2..board.width() - 2;
+        let top = (0..floor)
+            .find(|&y| columns.clone().any(|x| board.get(x, y) != TileType::Blank))
+            .unwrap_or(floor - 1);
+        for y in top..floor {
+            for x in columns.clone() {
+                text.push(match board.get(x, y) {
+                    TileType::Tet => in_play.unwrap_or('.'),
+                    TileType::Piece(t) => letter(pieces, t).unwrap_or('#'),
+                    tile if tile.is_filled() => '#',
+                    _ => '.',
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Diagram, String> {
+        Diagram::parse(text, Size::default(), &PieceSet::default())
+    }
+
+    #[test]
+    fn round_trip() {
+        let texts = [
+            "piece: T\nhold: I\nqueue: S Z O\n....t.....\n...ttt....\n#..##LL###\n######.###\n",
+            "piece: J\nqueue: I\nj.........\njjj.......\n",
+            "piece: I\n.........i\n.........i\n#########i\n#########i\n",
+            "#IIII#####\nOO.#######\n",
+        ];
+        for text in texts.iter() {
+            let diagram = parse(text).unwrap();
+            assert_eq!(diagram.to_text(&PieceSet::default()), *text);
+        }
+    }
+
+    #[test]
+    fn named_piece_comes_in_at_the_top() {
+        let pieces = PieceSet::default();
+        let diagram = parse("piece: O\n#########.\n").unwrap();
+        let piece = diagram.piece.unwrap();
+        let spawned = pieces.spawn(piece.tet_type);
+        assert_eq!((piece.x, piece.y), (spawned.x, spawned.y));
+        assert!(diagram.held.is_none());
+        assert!(diagram.queue.is_empty());
+    }
+
+    #[test]
+    fn bad_diagrams() {
+        // Too narrow, a piece we don't have, two pieces in play, a
+        // piece drawn that isn't the one named and a shape that isn't
+        // a piece.
+        assert!(parse("#########\n").is_err());
+        assert!(parse("queue: Q\n").is_err());
+        assert!(parse("t.........\ntt.......o\n").is_err());
+        assert!(parse("piece: S\n.t........\nttt.......\n").is_err());
+        assert!(parse("tt........\ntt........\n").is_err());
+        assert!(parse("colour: red\n").is_err());
+    }
+}
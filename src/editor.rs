@@ -0,0 +1,390 @@
+use clipboard::{ClipboardContext, ClipboardProvider};
+use ggez::event::{KeyCode, KeyMods, MouseButton};
+use ggez::graphics;
+use ggez::input::keyboard;
+use ggez::{Context, GameResult};
+
+use crate::board::Size;
+use crate::diagram::Diagram;
+use crate::engine::{plot_tet, validate_move, Board, Game, Piece, TileType};
+use crate::fumen::{self, Page};
+use crate::mode::Mode;
+use crate::pieces::{PieceSet, PieceType, BOX};
+use crate::play::PlayScene;
+use crate::scene::{Input, Scene, Shared, Transition};
+use crate::{
+    draw_board_contents, draw_board_grid, draw_preview_contents, draw_preview_grid, draw_text,
+};
+
+// The board editor, for building a position to practise from.  The
+// stack is painted with the mouse, and the piece in play, the held
+// piece and the pieces to come are picked with the keyboard.  The
+// position is then played as a sandbox game, and kept to play again
+// or to carry on editing.  Positions can be pasted in and copied out
+// as board diagrams or fumen, to share them.
+
+// A position built in the editor.
+#[derive(Debug, Clone)]
+pub struct Setup {
+    pub board: Board,
+    // The piece to play first.  Where it is only matters in the editor,
+    // for stamping it onto the stack.
+    pub piece: Piece,
+    pub held: Option<PieceType>,
+    // The pieces to come after it, before the bag takes over.
+    pub queue: Vec<PieceType>,
+}
+
+impl Setup {
+    // An empty playfield with the first piece of the set to play.
+    pub fn new(size: Size, pieces: &PieceSet) -> Setup {
+        Setup {
+            board: Board::playfield(size),
+            piece: pieces.spawn(PieceType(0)),
+            held: None,
+            queue: Vec::new(),
+        }
+    }
+
+    // Start a game from this position.
+    pub fn apply(&self, game: &mut Game) {
+        game.set_up(
+            self.board.clone(),
+            self.piece.tet_type,
+            self.held,
+            &self.queue,
+        );
+    }
+}
+
+pub struct EditorScene {
+    setup: Setup,
+    size: Size,
+    pieces: PieceSet,
+    // What the left mouse button paints with.
+    brush: TileType,
+    // What is being painted while a button is held down, if anything.
+    painting: Option<TileType>,
+    // The pages of the last fumen pasted in, and the one being shown.
+    pages: Vec<Page>,
+    page: usize,
+    // How the last paste or copy went.
+    message: String,
+}
+
+impl EditorScene {
+    // Carry on with the last position if it is for the same size of
+    // board, otherwise start with an empty one.
+    pub fn new(shared: &mut Shared) -> EditorScene {
+        let size = shared.options.size;
+        let pieces = shared
+            .options
+            .pieces
+            .set(shared.piece_set.as_ref())
+            .sized(size);
+        shared.layout = shared.layout.with_size(size);
+        let fresh = Board::playfield(size);
+        let setup = match &shared.setup {
+            Some(setup)
+                if setup.board.width() == fresh.width()
+                    && setup.board.height() == fresh.height() =>
+            {
+                setup.clone()
+            }
+            _ => Setup::new(size, &pieces),
+        };
+        EditorScene {
+            setup,
+            size,
+            pieces,
+            brush: TileType::Base,
+            painting: None,
+            pages: Vec::new(),
+            page: 0,
+            message: String::new(),
+        }
+    }
+
+    // The brushes in turn: the stack, garbage, then each piece's colour.
+    fn next_brush(&mut self) {
+        self.brush = match self.brush {
+            TileType::Base => TileType::Garbage,
+            TileType::Garbage => TileType::Piece(PieceType(0)),
+            TileType::Piece(t) if (t.0 as usize) + 1 < self.pieces.len() => {
+                TileType::Piece(PieceType(t.0 + 1))
+            }
+            _ => TileType::Base,
+        };
+    }
+
+    fn brush_name(&self) -> String {
+        match self.brush {
+            TileType::Garbage => "garbage".to_string(),
+            TileType::Piece(t) => self.pieces.name(t).to_string(),
+            _ => "stack".to_string(),
+        }
+    }
+
+    // The piece after this one in the set, going round.
+    fn next_type(&self, tet_type: PieceType) -> PieceType {
+        PieceType(((tet_type.0 as usize + 1) % self.pieces.len()) as u8)
+    }
+
+    fn paint(&mut self, shared: &Shared, x: f32, y: f32) {
+        if let (Some(tile), Some((bx, by))) = (self.painting, shared.layout.board_cell(x, y)) {
+            self.setup.board.set(bx, by, tile);
+        }
+    }
+
+    // Move or turn the piece, if it fits where it goes.
+    fn move_piece(&mut self, dx: i32, dy: i32, turn: bool) {
+        let x = self.setup.piece.x as i32 + dx;
+        let y = self.setup.piece.y as i32 + dy;
+        if x < 0 || y < 0 {
+            return;
+        }
+        let mut moved = self.setup.piece;
+        moved.x = x as usize;
+        moved.y = y as usize;
+        if turn {
+            moved.rotation = (moved.rotation + 1) % 4;
+        }
+        if validate_move(&self.setup.board, moved) {
+            self.setup.piece = moved;
+        }
+    }
+
+    // Stamp the piece onto the stack where it is, in its colour, and
+    // bring the same piece in again at the top.
+    fn stamp(&mut self) {
+        let piece = self.setup.piece;
+        if validate_move(&self.setup.board, piece) {
+            plot_tet(
+                &mut self.setup.board,
+                piece,
+                TileType::Piece(piece.tet_type),
+            );
+            self.setup.piece = self.pieces.spawn(piece.tet_type);
+        }
+    }
+
+    // Load a position from the clipboard, either a fumen, showing its
+    // first page, or a board diagram.
+    fn paste(&mut self) {
+        let text = match clipboard()
+            .and_then(|mut clipboard| clipboard.get_contents().map_err(|e| e.to_string()))
+        {
+            Ok(text) => text,
+            Err(e) => {
+                self.message = format!("Unable to paste: {}", e);
+                return;
+            }
+        };
+        if text.contains("v115@") {
+            match fumen::decode(&text, self.size, &self.pieces) {
+                Ok(pages) => {
+                    self.pages = pages;
+                    self.show_page(0);
+                }
+                Err(e) => self.message = format!("Not a fumen: {}", e),
+            }
+            return;
+        }
+        match Diagram::parse(&text, self.size, &self.pieces) {
+            Ok(diagram) => {
+                self.pages.clear();
+                self.setup.board = diagram.board;
+                self.setup.piece = diagram
+                    .piece
+                    .unwrap_or_else(|| self.pieces.spawn(self.setup.piece.tet_type));
+                self.setup.held = diagram.held;
+                self.setup.queue = diagram.queue;
+                self.message = "Pasted".to_string();
+            }
+            Err(e) => self.message = format!("Not a board: {}", e),
+        }
+    }
+
+    // Put the position on the clipboard as a board diagram, or as a
+    // fumen, which leaves out the held piece and the queue.  A fumen
+    // that was pasted in is copied with all of its pages, the one shown
+    // as it is now.
+    fn copy(&mut self, as_fumen: bool) {
+        let text = if as_fumen {
+            let mut pages = self.pages.clone();
+            let page = Page {
+                board: self.setup.board.clone(),
+                piece: Some(self.setup.piece),
+                comment: pages.get(self.page).and_then(|page| page.comment.clone()),
+            };
+            match pages.get_mut(self.page) {
+                Some(shown) => *shown = page,
+                None => pages = vec![page],
+            }
+            fumen::encode(&pages, &self.pieces)
+        } else {
+            let diagram = Diagram {
+                board: self.setup.board.clone(),
+                piece: Some(self.setup.piece),
+                held: self.setup.held,
+                queue: self.setup.queue.clone(),
+            };
+            diagram.to_text(&self.pieces)
+        };
+        let result = clipboard()
+            .and_then(|mut clipboard| clipboard.set_contents(text).map_err(|e| e.to_string()));
+        self.message = match result {
+            Ok(()) if as_fumen => "Copied as a fumen".to_string(),
+            Ok(()) => "Copied".to_string(),
+            Err(e) => format!("Unable to copy: {}", e),
+        };
+    }
+
+    // Set the position to a page of the fumen.  The held piece and the
+    // queue stay as they are.
+    fn show_page(&mut self, page: usize) {
+        if let Some(shown) = self.pages.get(page) {
+            self.page = page;
+            self.setup.board = shown.board.clone();
+            self.setup.piece = shown
+                .piece
+                .unwrap_or_else(|| self.pieces.spawn(self.setup.piece.tet_type));
+            self.message = format!("Page {} of {}", page + 1, self.pages.len());
+            if let Some(comment) = &shown.comment {
+                self.message = format!("{}: {}", self.message, comment);
+            }
+        }
+    }
+
+    fn key(&mut self, keycode: KeyCode) {
+        match keycode {
+            KeyCode::B => self.next_brush(),
+            KeyCode::I => {
+                let next = self.next_type(self.setup.piece.tet_type);
+                self.setup.piece = self.pieces.spawn(next);
+            }
+            KeyCode::H => {
+                self.setup.held = match self.setup.held {
+                    None => Some(PieceType(0)),
+                    Some(t) if (t.0 as usize) + 1 < self.pieces.len() => Some(self.next_type(t)),
+                    Some(_) => None,
+                }
+            }
+            KeyCode::N => self.setup.queue.push(self.setup.piece.tet_type),
+            KeyCode::Back => {
+                self.setup.queue.pop();
+            }
+            KeyCode::A => self.move_piece(-1, 0, false),
+            KeyCode::D => self.move_piece(1, 0, false),
+            KeyCode::S => self.move_piece(0, 1, false),
+            KeyCode::X => self.move_piece(0, -1, false),
+            KeyCode::W => self.move_piece(0, 0, true),
+            KeyCode::C => self.stamp(),
+            KeyCode::PageUp if self.page > 0 => self.show_page(self.page - 1),
+            KeyCode::PageDown => self.show_page(self.page + 1),
+            KeyCode::R => {
+                let board = &mut self.setup.board;
+                for y in 0..board.height() - 2 {
+                    board.clear_row(y);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Scene for EditorScene {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    fn input(&mut self, ctx: &mut Context, shared: &mut Shared, input: Input) -> Transition {
+        let ctrl = keyboard::is_mod_active(ctx, KeyMods::CTRL);
+        let shift = keyboard::is_mod_active(ctx, KeyMods::SHIFT);
+        match input {
+            Input::MouseDown(button, x, y) => {
+                self.painting = match button {
+                    MouseButton::Left => Some(self.brush),
+                    MouseButton::Right => Some(TileType::Blank),
+                    _ => None,
+                };
+                self.paint(shared, x, y);
+            }
+            Input::MouseMove(x, y) => self.paint(shared, x, y),
+            Input::MouseUp(_) => self.painting = None,
+            Input::Key(KeyCode::Return) => {
+                shared.setup = Some(self.setup.clone());
+                return Transition::Replace(Box::new(PlayScene::new(shared, Mode::Sandbox)));
+            }
+            Input::Key(KeyCode::Escape) => {
+                shared.setup = Some(self.setup.clone());
+                return Transition::Pop;
+            }
+            Input::Key(KeyCode::V) if ctrl => self.paste(),
+            Input::Key(KeyCode::C) if ctrl => self.copy(shift),
+            Input::Key(keycode) => self.key(keycode),
+            _ => (),
+        }
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult {
+        let layout = shared.layout;
+        let setup = &self.setup;
+        let queue: Vec<&str> = setup.queue.iter().map(|&t| self.pieces.name(t)).collect();
+        let lines = vec![
+            "Editor".to_string(),
+            format!("Brush: {}", self.brush_name()),
+            format!("Piece: {}", self.pieces.name(setup.piece.tet_type)),
+            format!(
+                "Hold: {}",
+                setup.held.map_or("none", |t| self.pieces.name(t))
+            ),
+            format!("Queue: {}", queue.join(" ")),
+            self.message.clone(),
+            "Left mouse: paint".to_string(),
+            "Right mouse: erase".to_string(),
+            "B: brush".to_string(),
+            "I: piece, H: hold".to_string(),
+            "N: queue the piece".to_string(),
+            "Backspace: unqueue".to_string(),
+            "A D S X: move, W: turn".to_string(),
+            "C: stamp the piece".to_string(),
+            "R: clear the board".to_string(),
+            "Ctrl+V: paste".to_string(),
+            "Ctrl+C: copy, with shift as fumen".to_string(),
+            "Page up/down: fumen pages".to_string(),
+            "Enter: play, Esc: back".to_string(),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            let y = layout.stats.y + i as f32 * layout.line_height();
+            draw_text(ctx, &layout, line.clone(), (layout.stats.x, y))?;
+        }
+
+        let mb = &mut graphics::MeshBuilder::new();
+        draw_board_grid(mb, &layout)?;
+        let mut board = setup.board.clone();
+        plot_tet(&mut board, setup.piece, TileType::Tet);
+        draw_board_contents(mb, &layout, &board)?;
+
+        draw_preview_grid(mb, &layout, layout.next)?;
+        if let Some(&next) = setup.queue.first() {
+            let mut next_board = Board::new(BOX, BOX);
+            plot_tet(&mut next_board, self.pieces.preview(next), TileType::Tet);
+            draw_preview_contents(mb, &layout, layout.next, &next_board)?;
+        }
+        draw_preview_grid(mb, &layout, layout.hold)?;
+        if let Some(held) = setup.held {
+            let mut hold_board = Board::new(BOX, BOX);
+            plot_tet(&mut hold_board, self.pieces.preview(held), TileType::Tet);
+            draw_preview_contents(mb, &layout, layout.hold, &hold_board)?;
+        }
+
+        let m = mb.build(ctx)?;
+        graphics::draw(ctx, &m, graphics::DrawParam::new())
+    }
+}
+
+fn clipboard() -> Result<ClipboardContext, String> {
+    ClipboardProvider::new().map_err(|e| e.to_string())
+}
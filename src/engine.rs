@@ -0,0 +1,1605 @@
+use std::collections::VecDeque;
+use std::mem;
+use std::time::Duration;
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+
+use crate::event::{ClearKind, Direction, GameEvent, GameOverReason};
+use crate::gravity::{Gravity, FRAMES_PER_SECOND, MAX_ROWS_PER_FRAME};
+use crate::pieces::{PieceSet, PieceType, Shape, BOX};
+use crate::scoring::{Clear, ScoreTable, ScoringSystem};
+use crate::spin::{detect, kicks, Kicks, Spin};
+
+// How long a piece can rest on the stack before it locks, unless the
+// gravity is so slow that falling a row takes longer.
+const LOCK_DELAY: Duration = Duration::from_millis(500);
+
+// How many snapshots the undo history holds: the piece in play and
+// the ones before it that can be taken back.
+pub const MAX_UNDO: usize = 50;
+
+// We use a 2d array (basically) to hold the board state and to know
+// where to draw the pieces, the base (pieces that hit the floor) and
+// the borders of the playing field.
+// The playfield size is set by the rules, 10x20 with 20 hidden rows
+// above unless they say otherwise.  We have additional space in the array for reasons that
+// involve how we plot the tetrominoes and translate their x,y location
+// when plotting on the board, see Size in board.rs.
+pub use crate::board::{Board, Size};
+
+// Each board square can be one of these choices.  Garbage is like
+// the base but came in from the bottom of the board rather than being
+// built up from pieces.  Locked pieces keep their colour, and squares
+// painted in the editor can be the colour of a piece too.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum TileType {
+    Border,
+    Tet,
+    Base,
+    Garbage,
+    Piece(PieceType),
+    Blank,
+}
+
+impl TileType {
+    // Whether the square is part of the stack, counting towards a full
+    // row.
+    pub fn is_filled(self) -> bool {
+        matches!(
+            self,
+            TileType::Base | TileType::Garbage | TileType::Piece(_)
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Piece {
+    pub tet_type: PieceType,
+    pub rotation: u8,
+    pub x: usize,
+    pub y: usize,
+    // The piece's shape in every rotation, from its piece set.
+    pub shape: Shape,
+}
+
+impl Piece {
+    // The rows of the shape at the piece's rotation.
+    pub fn rows(&self) -> [u8; BOX] {
+        self.shape[(self.rotation % 4) as usize]
+    }
+}
+
+// Board state is used to indicate the different states that the
+// board can be in during game play.  Specific states will change how
+// things are displayed and what movement is allowed.
+//
+// Moving: The normal state of things, the player can rotate
+// pieces, and pieces drop at every interval.
+//
+// Clearing: We freeze the board for one interval during which a
+// completed row is blanked out.  At the end of Clearing state, the
+// pieces above a cleared row fall to fill the empty rows.
+//
+// Paused: The player has requested a pause, no falling
+// or rotation is allowed.  The piece holds at its current location
+// and no bounds checking is performed.
+//
+// Over: The game was lost, see GameOverReason for how, or the line
+// goal has been reached.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum BoardState {
+    Moving,
+    Clearing,
+    Paused,
+    Over,
+}
+
+// The board squares covered by the piece at its location and rotation,
+// from the rows of its shape.
+pub fn tet_points(piece: Piece) -> Vec<(usize, usize)> {
+    let mut points = Vec::with_capacity(BOX);
+    for (dy, &mask) in piece.rows().iter().enumerate() {
+        for dx in 0..BOX {
+            if mask & (1 << dx) != 0 {
+                points.push((piece.x + dx, piece.y + dy));
+            }
+        }
+    }
+    points
+}
+
+// The piece moved, in the same rotation, to cover these squares, if
+// they are its shape.
+pub fn moved_onto(piece: Piece, squares: &[(usize, usize)]) -> Option<Piece> {
+    let mut points = tet_points(piece);
+    let mut squares = squares.to_vec();
+    if points.is_empty() || points.len() != squares.len() {
+        return None;
+    }
+    points.sort_by_key(|&(x, y)| (y, x));
+    squares.sort_by_key(|&(x, y)| (y, x));
+    let dx = squares[0].0 as i32 - points[0].0 as i32;
+    let dy = squares[0].1 as i32 - points[0].1 as i32;
+    let same = points
+        .iter()
+        .zip(squares.iter())
+        .all(|(&(px, py), &(sx, sy))| px as i32 + dx == sx as i32 && py as i32 + dy == sy as i32);
+    let (x, y) = (piece.x as i32 + dx, piece.y as i32 + dy);
+    if !same || x < 0 || y < 0 {
+        return None;
+    }
+    Some(Piece {
+        x: x as usize,
+        y: y as usize,
+        ..piece
+    })
+}
+
+// Check to see if the piece in the requested location and rotation
+// will fit on the given board.
+pub fn validate_move(board: &Board, piece: Piece) -> bool {
+    board.fits(piece)
+}
+
+// On the given board, draw the shape of the piece of
+// the given type in the given rotation.
+// The new_type can be any supported tile type, including blank
+pub fn plot_tet(board: &mut Board, piece: Piece, new_type: TileType) {
+    for (dy, &mask) in piece.rows().iter().enumerate() {
+        for dx in 0..BOX {
+            if mask & (1 << dx) != 0 {
+                board.set(piece.x + dx, piece.y + dy, new_type);
+            }
+        }
+    }
+}
+
+// The piece turned clockwise on the board, kicked to one side or up
+// or down if it doesn't fit where it is, and which kick it took.  None
+// if it doesn't fit anywhere.
+pub fn rotated(board: &Board, piece: Piece, table: Kicks) -> Option<(Piece, usize)> {
+    let mut turned = piece;
+    turned.rotation = (turned.rotation + 1) % 4;
+    for (i, (dx, dy)) in kicks(piece, table).iter().enumerate() {
+        let x = turned.x as i32 + dx;
+        let y = turned.y as i32 + dy;
+        if x < 0 || y < 0 {
+            continue;
+        }
+        let kicked = Piece {
+            x: x as usize,
+            y: y as usize,
+            ..turned
+        };
+        if validate_move(board, kicked) {
+            return Some((kicked, i));
+        }
+    }
+    None
+}
+
+// Call this when the active piece has hit something below it and can move
+// down no further.  We convert the squares the piece contained to the base
+// type, then we check to see if any rows have been filled.  Both the lock
+// and any filled rows are added to the list of events.
+//
+// A filled row,       return BoardState::Clearing
+// No filled rows,     return BoardState::Moving (but see below)
+//
+// If we don't clear rows, then we generate the next piece at the top of
+// the board.  If we are clearing, then don't generate the next piece.
+//
+// If we are not clearing rows, then it's possible that the placement of
+// the new piece will fail, in that case the tail call will end up
+// returning BoardState::Over
+//
+fn convert_and_check(
+    board: &mut Board,
+    piece: &mut Piece,
+    piece_queue: &mut TetQueue,
+    next_board: &mut Board,
+    next_piece: &mut Piece,
+    pieces: &PieceSet,
+    events: &mut Vec<GameEvent>,
+) -> BoardState {
+    // Redraw the piece as part of the stack, keeping its colour
+    plot_tet(board, *piece, TileType::Piece(piece.tet_type));
+    events.push(GameEvent::Locked { piece: *piece });
+
+    // See if there are any "full" rows.
+    let full_rows: Vec<usize> = (piece.y..board.height() - 2)
+        .filter(|&y| board.is_full(y))
+        .collect();
+    if !full_rows.is_empty() {
+        // We have at least one full row, go ahead and tell the caller the
+        // new state.  The rows are removed once the clearing is done.
+        let count = full_rows.len() as u32;
+        events.push(GameEvent::LinesCleared {
+            count,
+            kind: ClearKind::from_lines(count),
+            rows: full_rows,
+        });
+        return BoardState::Clearing;
+    }
+
+    // We can go ahead with placing a new piece now, return the
+    // result of this call
+    place_new_piece(
+        board,
+        piece,
+        piece_queue,
+        next_board,
+        next_piece,
+        pieces,
+        events,
+    )
+}
+
+// This starts a new piece moving down from the top of the
+// board.  We also check for game over if the new piece has
+// no empty squares to be placed in.
+fn place_new_piece(
+    board: &mut Board,
+    piece: &mut Piece,
+    piece_queue: &mut TetQueue,
+    next_board: &mut Board,
+    next_piece: &mut Piece,
+    pieces: &PieceSet,
+    events: &mut Vec<GameEvent>,
+) -> BoardState {
+    let mut res = BoardState::Moving;
+
+    *piece = pieces.spawn(piece_queue.next());
+
+    // Clear the old and and fill the next_board array with the next piece
+    plot_tet(next_board, *next_piece, TileType::Blank);
+    *next_piece = pieces.preview(piece_queue.peek());
+    plot_tet(next_board, *next_piece, TileType::Tet);
+
+    if validate_move(board, *piece) {
+        events.push(GameEvent::PieceSpawned {
+            piece: *piece,
+            next: next_piece.tet_type,
+        });
+    } else {
+        res = BoardState::Over;
+    }
+    res
+}
+
+// Try to move tet down.  If it works, then return true.
+// If the move is not valid, then return false.
+fn move_tet_down(board: &mut Board, piece: &mut Piece) -> bool {
+    piece.y += 1;
+    if validate_move(board, *piece) {
+        piece.y -= 1;
+        plot_tet(board, *piece, TileType::Blank);
+        piece.y += 1;
+        return true;
+    } // else
+    piece.y -= 1;
+    false
+}
+
+// This struct keeps track of the coming pieces.
+// We randomly shuffle the bag of pieces from the piece set, then put
+// them on a queue whenever the queue gets low.
+// This also enables us to print the next tetrominoe.
+// The random numbers come from a seed, so the same seed always gives
+// the same pieces in the same order.
+// #[derive(Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "SavedQueue", into = "SavedQueue")]
+pub struct TetQueue {
+    q: VecDeque<PieceType>,
+    x: Vec<PieceType>,
+    rng: Pcg32,
+}
+
+// The queue as it is saved, with the pieces on it as a list.
+#[derive(Serialize, Deserialize)]
+struct SavedQueue {
+    coming: Vec<PieceType>,
+    bag: Vec<PieceType>,
+    rng: Pcg32,
+}
+
+impl From<TetQueue> for SavedQueue {
+    fn from(queue: TetQueue) -> SavedQueue {
+        SavedQueue {
+            coming: queue.coming(),
+            bag: queue.x,
+            rng: queue.rng,
+        }
+    }
+}
+
+impl From<SavedQueue> for TetQueue {
+    fn from(saved: SavedQueue) -> TetQueue {
+        TetQueue {
+            q: saved.coming.into_iter().collect(),
+            x: saved.bag,
+            rng: saved.rng,
+        }
+    }
+}
+
+// We use the random bag method for selecting the next tet.
+impl TetQueue {
+    pub fn new(seed: u64, bag: Vec<PieceType>) -> TetQueue {
+        let mut q = VecDeque::new();
+        let mut x = bag;
+
+        let mut rng = Pcg32::seed_from_u64(seed);
+        x.shuffle(&mut rng);
+        for tet in &x {
+            q.push_back(*tet);
+        }
+        TetQueue { q, x, rng }
+    }
+    pub fn next(&mut self) -> PieceType {
+        if self.q.len() <= 1 {
+            self.x.shuffle(&mut self.rng);
+            for tet in &self.x {
+                self.q.push_back(*tet);
+            }
+        }
+        self.q.pop_front().unwrap()
+    }
+    pub fn peek(&mut self) -> PieceType {
+        self.q[0]
+    }
+
+    // The pieces on the queue, in the order they come.
+    pub fn coming(&self) -> Vec<PieceType> {
+        self.q.iter().copied().collect()
+    }
+
+    // Put these pieces at the front of the queue, to come before any
+    // already on it.
+    pub fn put_first(&mut self, pieces: &[PieceType]) {
+        for &tet in pieces.iter().rev() {
+            self.q.push_front(tet);
+        }
+    }
+}
+
+// What happens when there is no room for the next piece.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum TopOut {
+    // The game is over.
+    End,
+    // The board is emptied and play goes on.
+    ClearBoard,
+}
+
+// The rules that make one mode of play different from another: when
+// the game is won, how fast the pieces fall and whether it can be
+// lost.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Rules {
+    // The game is won once this many lines are cleared.
+    pub line_goal: Option<u32>,
+    // The game ends once it has been played this long.
+    pub time_limit: Option<Duration>,
+    pub gravity: Gravity,
+    pub top_out: TopOut,
+    // Rows of garbage on the board at the start.
+    pub garbage_rows: u32,
+    // Another row of garbage comes in from the bottom this often.
+    pub garbage_every: Option<Duration>,
+    // The game is won once all of the garbage has been cleared.
+    pub clear_garbage: bool,
+    // Spins count for every piece, not just T.
+    pub all_spin: bool,
+    pub size: Size,
+    // Pieces can be taken back, for practice.
+    pub undo: bool,
+}
+
+impl Default for Rules {
+    // Endless play, getting faster until the pieces reach the top.
+    fn default() -> Rules {
+        Rules {
+            line_goal: None,
+            time_limit: None,
+            gravity: Gravity::Guideline,
+            top_out: TopOut::End,
+            garbage_rows: 0,
+            garbage_every: None,
+            clear_garbage: false,
+            all_spin: false,
+            size: Size::default(),
+            undo: false,
+        }
+    }
+}
+
+// The things a player, or anything else driving the game, can do.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    Rotate,
+    Hold,
+    Pause,
+    // Take back the last piece, if the rules allow it.
+    Undo,
+}
+
+// The whole state of one game.  The game is moved along by applying
+// actions and by gravity ticks, and as things happen it records
+// events that are collected with take_events().
+//
+// All of it can be saved, except for the scoring system, which the
+// game is given again when it is loaded.
+#[derive(Serialize, Deserialize)]
+pub struct Game {
+    // The main game board
+    pub board: Board,
+    // The place where we render the next piece
+    pub next_board: Board,
+    pub piece: Piece,
+    pub next_piece: Piece,
+    pub piece_queue: TetQueue,
+    // Where the random piece order came from
+    pub seed: u64,
+    // The piece put aside with hold, and whether hold has been used
+    // since the last piece locked.
+    pub held: Option<PieceType>,
+    hold_used: bool,
+    pub board_state: BoardState,
+    pub score: u32,
+    pub level: u32,
+    // Lines cleared in the whole game.
+    pub lines: u32,
+    pub rules: Rules,
+    // What everything scores, and when the level goes up.
+    #[serde(skip, default = "default_scoring")]
+    pub scoring: Box<dyn ScoringSystem>,
+    // The shapes of the pieces and where they come in.
+    pub pieces: PieceSet,
+    // How long the game has been played, not counting pauses.  This
+    // is real time handed in by the caller, so it doesn't depend on
+    // the frame rate or the gravity speed.
+    pub elapsed: Duration,
+    // The game time when the last piece locked, which is when its
+    // lines count as cleared.
+    locked_at: Duration,
+    // When the next row of garbage comes in, and where the holes in
+    // the garbage come from.
+    next_garbage: Duration,
+    garbage_rng: Pcg32,
+    // Rows of garbage that arrived while the board was clearing or
+    // paused, brought in once there is a piece in play again.
+    #[serde(default)]
+    garbage_waiting: u32,
+    // Gravity built up towards the next row, and how long the piece
+    // has been resting on the stack.
+    fall: f32,
+    resting: Duration,
+    // The kick used if the last thing the piece did was turn, and the
+    // spin it locked with, waiting to be scored with its lines.
+    last_kick: Option<usize>,
+    spin: Option<Spin>,
+    // Pieces in a row that have cleared lines, and difficult clears
+    // (tetrises and spins) in a row with no easier clear between.
+    pub combo: u32,
+    pub back_to_back: u32,
+    // How many times a piece was taken back, and the game as it was
+    // when each of the last few pieces came in, the piece in play
+    // last.  The history isn't saved, a continued game starts without
+    // one.
+    pub undos: u32,
+    #[serde(skip)]
+    history: Vec<Snapshot>,
+    #[serde(skip)]
+    events: Vec<GameEvent>,
+}
+
+// Everything an undo puts back.  The boards are plain values, so this
+// is cheap enough to keep one for every piece.  The game clock and the
+// garbage timer carry on, only the pieces and the stack go back.
+#[derive(Clone)]
+struct Snapshot {
+    board: Board,
+    next_board: Board,
+    piece: Piece,
+    next_piece: Piece,
+    piece_queue: TetQueue,
+    held: Option<PieceType>,
+    hold_used: bool,
+    score: u32,
+    level: u32,
+    lines: u32,
+    combo: u32,
+    back_to_back: u32,
+    garbage_rng: Pcg32,
+}
+
+// Stands in for the scoring system of a loaded game until it is given
+// the real one.
+fn default_scoring() -> Box<dyn ScoringSystem> {
+    Box::new(ScoreTable::default())
+}
+
+impl Game {
+    pub fn new(seed: u64, rules: Rules, scoring: Box<dyn ScoringSystem>, pieces: PieceSet) -> Game {
+        let pieces = pieces.sized(rules.size);
+        let mut q = TetQueue::new(seed, pieces.bag());
+
+        let piece = pieces.spawn(q.next());
+        let next_piece = pieces.preview(q.peek());
+
+        let board = Board::playfield(rules.size);
+        let mut next_board = Board::new(BOX, BOX);
+        plot_tet(&mut next_board, next_piece, TileType::Tet);
+
+        let mut game = Game {
+            board,
+            next_board,
+            piece,
+            next_piece,
+            piece_queue: q,
+            seed,
+            held: None,
+            hold_used: false,
+            board_state: BoardState::Moving,
+            score: 0,
+            level: 0,
+            lines: 0,
+            rules,
+            scoring,
+            pieces,
+            elapsed: Duration::from_secs(0),
+            locked_at: Duration::from_secs(0),
+            next_garbage: rules.garbage_every.unwrap_or_default(),
+            garbage_waiting: 0,
+            // A different stream from the same seed, so the holes don't
+            // follow the pieces.
+            garbage_rng: Pcg32::new(seed, 0x6761_7262_6167_6521),
+            fall: 0.0,
+            resting: Duration::from_secs(0),
+            last_kick: None,
+            spin: None,
+            combo: 0,
+            back_to_back: 0,
+            undos: 0,
+            history: Vec::new(),
+            events: Vec::new(),
+        };
+        // The first piece can be blocked as well as any other
+        if !validate_move(&game.board, piece) {
+            game.game_over(GameOverReason::BlockedSpawn);
+            return game;
+        }
+        game.events.push(GameEvent::PieceSpawned {
+            piece,
+            next: next_piece.tet_type,
+        });
+        game.add_garbage(rules.garbage_rows);
+        game.remember();
+        game.spawn_drop();
+        game
+    }
+
+    // Start from a position built in the editor: the stack, the piece
+    // to play, the one on hold and the pieces to come before the bag
+    // takes over.  A board of another size is left out.
+    pub fn set_up(
+        &mut self,
+        board: Board,
+        piece: PieceType,
+        held: Option<PieceType>,
+        queue: &[PieceType],
+    ) {
+        if board.width() == self.board.width() && board.height() == self.board.height() {
+            self.board = board;
+        }
+        self.piece = self.pieces.spawn(piece);
+        self.held = held;
+        self.piece_queue.put_first(queue);
+        plot_tet(&mut self.next_board, self.next_piece, TileType::Blank);
+        self.next_piece = self.pieces.preview(self.piece_queue.peek());
+        plot_tet(&mut self.next_board, self.next_piece, TileType::Tet);
+        self.history.clear();
+        self.events.clear();
+        if !validate_move(&self.board, self.piece) {
+            self.game_over(GameOverReason::BlockedSpawn);
+            return;
+        }
+        self.events.push(GameEvent::PieceSpawned {
+            piece: self.piece,
+            next: self.next_piece.tet_type,
+        });
+        self.remember();
+        self.spawn_drop();
+    }
+
+    // Hand over everything that has happened since the last call.
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        mem::take(&mut self.events)
+    }
+
+    // Let gravity act for the real time since the last call.  The
+    // piece falls as many rows as the speed has built up to, and locks
+    // once it has rested on the stack long enough.  Full rows waiting
+    // in the Clearing state are removed.
+    pub fn fall(&mut self, dt: Duration) {
+        match self.board_state {
+            BoardState::Clearing => return self.remove_full_rows(),
+            BoardState::Paused | BoardState::Over => return,
+            BoardState::Moving => (),
+        }
+        self.fall += self.rows_per_frame() * dt.as_secs_f32() * FRAMES_PER_SECOND;
+        while self.fall >= 1.0 {
+            self.fall -= 1.0;
+            if !move_tet_down(&mut self.board, &mut self.piece) {
+                self.fall = 0.0;
+                break;
+            }
+            self.resting = Duration::from_secs(0);
+            self.last_kick = None;
+        }
+
+        let mut below = self.piece;
+        below.y += 1;
+        if validate_move(&self.board, below) {
+            self.resting = Duration::from_secs(0);
+        } else {
+            self.resting += dt;
+            if self.resting >= self.lock_delay() {
+                self.lock_piece();
+            }
+        }
+    }
+
+    // The current speed in rows per frame.
+    pub fn rows_per_frame(&self) -> f32 {
+        self.rules.gravity.rows_per_frame(self.level, self.lines)
+    }
+
+    fn lock_delay(&self) -> Duration {
+        let row = Duration::from_secs_f32(1.0 / (self.rows_per_frame() * FRAMES_PER_SECOND));
+        row.max(LOCK_DELAY)
+    }
+
+    // A new piece drops a row as soon as it comes in, if there is
+    // room, so it starts out on the rows shown.  At 20G it comes in
+    // already sitting on the stack.
+    fn spawn_drop(&mut self) {
+        if self.board_state != BoardState::Moving {
+            return;
+        }
+        if self.rows_per_frame() >= MAX_ROWS_PER_FRAME {
+            while move_tet_down(&mut self.board, &mut self.piece) {}
+        } else {
+            move_tet_down(&mut self.board, &mut self.piece);
+        }
+    }
+
+    // Move the game clock on by the real time since the last call.
+    // The clock stops while paused and once the game is over.
+    pub fn advance(&mut self, dt: Duration) {
+        match self.board_state {
+            BoardState::Moving | BoardState::Clearing => self.elapsed += dt,
+            BoardState::Paused | BoardState::Over => return,
+        }
+        if let Some(limit) = self.rules.time_limit {
+            if self.elapsed >= limit {
+                self.finish(limit);
+            }
+        }
+        // Garbage waits for any clearing to finish before coming in
+        if self.board_state == BoardState::Moving && self.garbage_waiting > 0 {
+            let rows = mem::take(&mut self.garbage_waiting);
+            self.add_garbage(rows);
+        }
+        if let Some(every) = self.rules.garbage_every {
+            if self.board_state == BoardState::Moving && self.elapsed >= self.next_garbage {
+                self.next_garbage += every;
+                self.add_garbage(1);
+            }
+        }
+    }
+
+    // Push everything on the board up and bring in rows of garbage
+    // from the bottom, each one full except for a single hole.  Only a
+    // piece in play can be lifted out of the way, so at any other time
+    // the rows wait for the next call to advance.
+    pub fn add_garbage(&mut self, rows: u32) {
+        match self.board_state {
+            BoardState::Moving => (),
+            BoardState::Clearing | BoardState::Paused => {
+                self.garbage_waiting += rows;
+                return;
+            }
+            BoardState::Over => return,
+        }
+        let floor = self.board.height() - 2;
+        let rows = (rows as usize).min(floor);
+        if rows == 0 {
+            return;
+        }
+        plot_tet(&mut self.board, self.piece, TileType::Blank);
+
+        // Anything pushed off the top is lost, which ends the game.
+        let overflow = (0..rows).any(|y| !self.board.is_empty(y));
+        for y in rows..floor {
+            self.board.copy_row(y, y - rows);
+        }
+        for y in floor - rows..floor {
+            let hole = self.garbage_rng.gen_range(2, self.board.width() - 2);
+            for x in 2..self.board.width() - 2 {
+                let tile = if x == hole {
+                    TileType::Blank
+                } else {
+                    TileType::Garbage
+                };
+                self.board.set(x, y, tile);
+            }
+        }
+        self.events
+            .push(GameEvent::GarbageAdded { rows: rows as u32 });
+
+        // Lift the piece clear if the stack came up into it
+        while !validate_move(&self.board, self.piece) && self.piece.y > 0 {
+            self.piece.y -= 1;
+        }
+        if overflow || !validate_move(&self.board, self.piece) {
+            self.game_over(GameOverReason::PushedOff);
+        }
+    }
+
+    // Nothing left on the board at all.  The piece in play isn't part
+    // of the board yet.
+    fn board_empty(&self) -> bool {
+        (0..self.board.height() - 2).all(|y| self.board.is_empty(y))
+    }
+
+    // How many rows still have garbage in them.
+    pub fn garbage_left(&self) -> u32 {
+        let board = &self.board;
+        (0..board.height() - 2)
+            .filter(|&y| (2..board.width() - 2).any(|x| board.get(x, y) == TileType::Garbage))
+            .count() as u32
+    }
+
+    // Do what the player asked.  Returns true if the action did
+    // anything.
+    pub fn apply(&mut self, action: Action) -> bool {
+        // Only a game that is in play can be paused, so a finished game
+        // stays finished.
+        if action == Action::Pause {
+            match self.board_state {
+                BoardState::Paused => {
+                    self.board_state = BoardState::Moving;
+                    self.events.push(GameEvent::Resumed);
+                }
+                BoardState::Moving => {
+                    self.board_state = BoardState::Paused;
+                    self.events.push(GameEvent::Paused);
+                }
+                _ => return false,
+            }
+            return true;
+        }
+
+        match action {
+            Action::Rotate => self.rotate(),
+            Action::MoveLeft => {
+                // The I piece has a rotation that could have an x value
+                // of zero, so we have to prevent it underflowing
+                if self.piece.x == 0 {
+                    return false;
+                }
+                let mut moved = self.piece;
+                moved.x -= 1;
+                self.try_move(moved, Some(Direction::Left))
+            }
+            Action::MoveRight => {
+                let mut moved = self.piece;
+                moved.x += 1;
+                self.try_move(moved, Some(Direction::Right))
+            }
+            Action::SoftDrop => {
+                if move_tet_down(&mut self.board, &mut self.piece) {
+                    self.last_kick = None;
+                    self.add_score(self.scoring.soft_drop(1));
+                    self.events.push(GameEvent::Moved {
+                        piece: self.piece,
+                        direction: Direction::Down,
+                    });
+                } else {
+                    self.lock_piece();
+                }
+                true
+            }
+            Action::HardDrop => {
+                let from = self.piece;
+                while move_tet_down(&mut self.board, &mut self.piece) {
+                    // XXX This needs to not do the convert and check yet,
+                    // let the timer run out (so we can slide to the side)
+                    // before making final the piece.
+                    self.last_kick = None;
+                }
+                self.events.push(GameEvent::HardDrop {
+                    from,
+                    to: self.piece,
+                });
+                let rows = (self.piece.y - from.y) as u32;
+                self.add_score(self.scoring.hard_drop(rows));
+                self.lock_piece();
+                true
+            }
+            Action::Hold => self.hold(),
+            Action::Undo => self.undo(),
+            Action::Pause => unreachable!(),
+        }
+    }
+
+    // Turn the piece clockwise, kicking it to one side or up or down
+    // if it doesn't fit where it is.
+    fn rotate(&mut self) -> bool {
+        let table = self.pieces.kicks(self.piece.tet_type);
+        match rotated(&self.board, self.piece, table) {
+            Some((turned, kick)) if self.try_move(turned, None) => {
+                self.last_kick = Some(kick);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Move the piece to a new location or rotation if it fits there.
+    // A direction means it was a move, none means a rotation.
+    fn try_move(&mut self, to: Piece, direction: Option<Direction>) -> bool {
+        if !validate_move(&self.board, to) {
+            return false;
+        }
+        if direction.is_some() {
+            self.last_kick = None;
+        }
+        plot_tet(&mut self.board, self.piece, TileType::Blank);
+        self.piece = to;
+        self.events.push(match direction {
+            Some(direction) => GameEvent::Moved {
+                piece: to,
+                direction,
+            },
+            None => GameEvent::Rotated { piece: to },
+        });
+        true
+    }
+
+    // Whether hold can be used on the piece in play.
+    pub fn can_hold(&self) -> bool {
+        !self.hold_used && self.board_state == BoardState::Moving
+    }
+
+    // Swap the current piece with the held one, or put it aside and
+    // take the next piece if nothing is held yet.  This can only be
+    // done once for each piece that comes in.
+    fn hold(&mut self) -> bool {
+        if !self.can_hold() {
+            return false;
+        }
+        plot_tet(&mut self.board, self.piece, TileType::Blank);
+        self.last_kick = None;
+        let held = self.piece.tet_type;
+        self.events.push(GameEvent::Hold { held });
+
+        match self.held.replace(held) {
+            Some(tet_type) => {
+                // Bring the held piece in at the top, the same way new
+                // pieces come in.
+                self.piece = self.pieces.spawn(tet_type);
+                if !validate_move(&self.board, self.piece) {
+                    self.game_over(GameOverReason::BlockedSpawn);
+                }
+            }
+            None => {
+                self.board_state = place_new_piece(
+                    &mut self.board,
+                    &mut self.piece,
+                    &mut self.piece_queue,
+                    &mut self.next_board,
+                    &mut self.next_piece,
+                    &self.pieces,
+                    &mut self.events,
+                );
+                if self.board_state == BoardState::Over {
+                    self.game_over(GameOverReason::BlockedSpawn);
+                }
+            }
+        }
+        self.hold_used = true;
+        self.spawn_drop();
+        true
+    }
+
+    // Put the game back to how it was when the last piece to lock came
+    // in, with that piece back at the top.
+    fn undo(&mut self) -> bool {
+        if !self.rules.undo || self.board_state != BoardState::Moving || self.history.len() < 2 {
+            return false;
+        }
+        self.history.pop();
+        let snapshot = self.history[self.history.len() - 1].clone();
+        self.board = snapshot.board;
+        self.next_board = snapshot.next_board;
+        self.piece = snapshot.piece;
+        self.next_piece = snapshot.next_piece;
+        self.piece_queue = snapshot.piece_queue;
+        self.held = snapshot.held;
+        self.hold_used = snapshot.hold_used;
+        self.score = snapshot.score;
+        self.level = snapshot.level;
+        self.lines = snapshot.lines;
+        self.combo = snapshot.combo;
+        self.back_to_back = snapshot.back_to_back;
+        self.garbage_rng = snapshot.garbage_rng;
+        self.fall = 0.0;
+        self.resting = Duration::from_secs(0);
+        self.last_kick = None;
+        self.spin = None;
+        self.undos += 1;
+        self.events.push(GameEvent::Undone { piece: self.piece });
+        self.spawn_drop();
+        true
+    }
+
+    // Keep the game as it is when a new piece comes in, to come back
+    // to if the piece is undone.  Anything drawn of the piece is left
+    // off the board.
+    fn remember(&mut self) {
+        if !self.rules.undo || self.board_state != BoardState::Moving {
+            return;
+        }
+        let mut board = self.board.clone();
+        plot_tet(&mut board, self.piece, TileType::Blank);
+        if self.history.len() >= MAX_UNDO {
+            self.history.remove(0);
+        }
+        self.history.push(Snapshot {
+            board,
+            next_board: self.next_board.clone(),
+            piece: self.piece,
+            next_piece: self.next_piece,
+            piece_queue: self.piece_queue.clone(),
+            held: self.held,
+            hold_used: self.hold_used,
+            score: self.score,
+            level: self.level,
+            lines: self.lines,
+            combo: self.combo,
+            back_to_back: self.back_to_back,
+            garbage_rng: self.garbage_rng.clone(),
+        });
+    }
+
+    // The piece can't move down any further, make it part of the base.
+    pub fn lock_piece(&mut self) {
+        self.hold_used = false;
+        self.locked_at = self.elapsed;
+        self.fall = 0.0;
+        self.resting = Duration::from_secs(0);
+        let locked = self.piece;
+        // Nothing of the piece made it down to the rows shown
+        let locked_out = tet_points(locked)
+            .iter()
+            .all(|&(_, y)| y < self.rules.size.hidden);
+        self.spin = detect(
+            &self.board,
+            locked,
+            self.last_kick.take(),
+            self.pieces.is_t(locked.tet_type),
+            self.rules.all_spin,
+        );
+        self.board_state = convert_and_check(
+            &mut self.board,
+            &mut self.piece,
+            &mut self.piece_queue,
+            &mut self.next_board,
+            &mut self.next_piece,
+            &self.pieces,
+            &mut self.events,
+        );
+        // A piece that clears nothing ends the combo
+        if self.board_state != BoardState::Clearing {
+            self.combo = 0;
+        }
+
+        if let Some(spin) = self.spin {
+            let lines = match self.events.last() {
+                Some(GameEvent::LinesCleared { count, .. }) => *count,
+                _ => 0,
+            };
+            self.events.push(GameEvent::Spun {
+                piece: locked.tet_type,
+                name: self.pieces.name(locked.tet_type).to_string(),
+                t_spin: self.pieces.is_t(locked.tet_type),
+                spin,
+                lines,
+            });
+            // A spin without lines scores now, with lines it scores
+            // once they are cleared.
+            if lines == 0 {
+                self.spin = None;
+                let clear = Clear {
+                    lines: 0,
+                    spin: Some(spin),
+                    combo: 0,
+                    back_to_back: false,
+                    perfect: false,
+                };
+                self.add_score(self.scoring.clear(&clear, self.level));
+            }
+        }
+        // A piece that clears lines brings the stack down, so it isn't
+        // locked out even if it was all above the rows shown.
+        if locked_out && self.board_state != BoardState::Clearing {
+            self.game_over(GameOverReason::LockedAbove);
+        } else if self.board_state == BoardState::Over {
+            self.game_over(GameOverReason::BlockedSpawn);
+        }
+        self.remember();
+        self.spawn_drop();
+    }
+
+    fn add_score(&mut self, points: u32) {
+        if points == 0 {
+            return;
+        }
+        self.score += points;
+        self.events.push(GameEvent::Scored {
+            points,
+            total: self.score,
+        });
+    }
+
+    // The game was lost, with the new piece already in place at the
+    // top.
+    fn game_over(&mut self, reason: GameOverReason) {
+        if self.rules.top_out == TopOut::ClearBoard {
+            for y in 0..self.board.height() - 2 {
+                self.board.clear_row(y);
+            }
+            self.board_state = BoardState::Moving;
+            self.events.push(GameEvent::BoardCleared);
+            // A blocked piece never came in, it does now
+            if reason == GameOverReason::BlockedSpawn {
+                self.events.push(GameEvent::PieceSpawned {
+                    piece: self.piece,
+                    next: self.next_piece.tet_type,
+                });
+            }
+            return;
+        }
+        self.board_state = BoardState::Over;
+        self.events.push(GameEvent::GameOver {
+            reason,
+            score: self.score,
+            level: self.level,
+            lines: self.lines,
+        });
+    }
+
+    // The goal was reached or the time is up.  The clock stops at the
+    // time given, which leaves out the clear animation a race finishes
+    // on.
+    fn finish(&mut self, time: Duration) {
+        self.elapsed = time;
+        self.board_state = BoardState::Over;
+        self.events.push(GameEvent::Finished {
+            time,
+            lines: self.lines,
+        });
+    }
+
+    // Once we have done one cycle clear, we then resume
+    // part movement and drop down pieces above our cleared
+    // row(s)
+    fn remove_full_rows(&mut self) {
+        // Nothing to clear, so nothing to score either, just bring in
+        // the next piece.
+        if !(0..self.board.height() - 2).any(|y| self.board.is_full(y)) {
+            return self.bring_in_next();
+        }
+
+        // Start from the bottom and work our way up.
+        // The destination Y always starts at the highest valid
+        // valid Y we can have pieces at.
+        // We walk the array from the bottom up.
+        let mut y_dest = self.board.height() - 3;
+        let mut cleared = 0;
+        for y in (0..self.board.height() - 2).rev() {
+            if self.board.is_full(y) {
+                cleared += 1;
+                continue;
+            }
+
+            // Move the source y to the current y.
+            self.board.copy_row(y, y_dest);
+
+            y_dest -= 1;
+        }
+        // Clear out any rows left at the top
+        for y in 0..=y_dest {
+            self.board.clear_row(y);
+        }
+
+        // Difficult clears in a row, and pieces in a row that clear
+        // lines, earn a bonus.
+        let spin = self.spin.take();
+        if cleared >= 4 || spin.is_some() {
+            self.back_to_back += 1;
+            if self.back_to_back > 1 {
+                self.events.push(GameEvent::BackToBack {
+                    count: self.back_to_back - 1,
+                });
+            }
+        } else {
+            self.back_to_back = 0;
+        }
+        self.combo += 1;
+        if self.combo > 1 {
+            self.events.push(GameEvent::Combo {
+                count: self.combo - 1,
+            });
+        }
+        let perfect = self.board_empty();
+        if perfect {
+            self.events.push(GameEvent::PerfectClear { lines: cleared });
+        }
+        let clear = Clear {
+            lines: cleared,
+            spin,
+            combo: self.combo - 1,
+            back_to_back: self.back_to_back > 1,
+            perfect,
+        };
+        self.add_score(self.scoring.clear(&clear, self.level));
+
+        // The scoring system says when the level goes up
+        let before = self.lines;
+        self.lines += cleared;
+        let level = self.scoring.level(0, self.lines).max(self.level);
+        if level > self.level {
+            self.level = level;
+            self.events.push(GameEvent::LevelUp { level: self.level });
+        }
+
+        if let Some(goal) = self.rules.line_goal {
+            // A split for every ten lines passed on the way to the goal
+            for split in (before / 10 + 1)..=(self.lines.min(goal) / 10) {
+                self.events.push(GameEvent::Split {
+                    lines: split * 10,
+                    time: self.locked_at,
+                });
+            }
+            if self.lines >= goal {
+                self.finish(self.locked_at);
+                return;
+            }
+        }
+        if self.rules.clear_garbage && self.garbage_left() == 0 {
+            self.finish(self.locked_at);
+            return;
+        }
+        self.bring_in_next();
+    }
+
+    // Bring the next piece in at the top, once any full rows are gone.
+    fn bring_in_next(&mut self) {
+        self.board_state = place_new_piece(
+            &mut self.board,
+            &mut self.piece,
+            &mut self.piece_queue,
+            &mut self.next_board,
+            &mut self.next_piece,
+            &self.pieces,
+            &mut self.events,
+        );
+        if self.board_state == BoardState::Over {
+            self.game_over(GameOverReason::BlockedSpawn);
+        }
+        self.remember();
+        self.spawn_drop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Diagram;
+
+    // The I stands in a well four rows deep, clearing them all.
+    const TETRIS: &str = "piece: I\n\
+                          .........i\n\
+                          #########i\n\
+                          #########i\n\
+                          #########i\n\
+                          #########.\n";
+
+    // A game set up from a diagram, with the piece in play where it is
+    // drawn.
+    fn game(text: &str) -> Game {
+        game_with(Rules::default(), text)
+    }
+
+    fn game_with(rules: Rules, text: &str) -> Game {
+        let mut game = Game::new(1, rules, default_scoring(), PieceSet::default());
+        lay_out(&mut game, text);
+        game
+    }
+
+    // Put the board and piece from a diagram into a game already going,
+    // which keeps its combo and back-to-back.
+    fn lay_out(game: &mut Game, text: &str) {
+        let diagram = Diagram::parse(text, Size::default(), &game.pieces).unwrap();
+        let piece = diagram.piece.unwrap();
+        game.set_up(diagram.board, piece.tet_type, diagram.held, &diagram.queue);
+        game.piece = piece;
+        game.take_events();
+    }
+
+    // Drop the piece and clear any rows it filled.
+    fn lock(game: &mut Game) -> Vec<GameEvent> {
+        assert!(game.apply(Action::HardDrop));
+        if game.board_state == BoardState::Clearing {
+            game.fall(Duration::from_secs(0));
+        }
+        game.take_events()
+    }
+
+    fn combo(events: &[GameEvent]) -> Option<u32> {
+        events.iter().find_map(|event| match event {
+            GameEvent::Combo { count } => Some(*count),
+            _ => None,
+        })
+    }
+
+    fn back_to_back(events: &[GameEvent]) -> Option<u32> {
+        events.iter().find_map(|event| match event {
+            GameEvent::BackToBack { count } => Some(*count),
+            _ => None,
+        })
+    }
+
+    // The stack on the board as a diagram, leaving out the piece in
+    // play.
+    fn stack(game: &Game) -> String {
+        let diagram = Diagram {
+            board: game.board.clone(),
+            piece: None,
+            held: None,
+            queue: Vec::new(),
+        };
+        diagram.to_text(&game.pieces)
+    }
+
+    fn cleared(events: &[GameEvent]) -> Option<u32> {
+        events.iter().find_map(|event| match event {
+            GameEvent::LinesCleared { count, .. } => Some(*count),
+            _ => None,
+        })
+    }
+
+    fn splits(events: &[GameEvent]) -> Vec<(u32, Duration)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::Split { lines, time } => Some((*lines, *time)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn spun(events: &[GameEvent]) -> Option<(Spin, u32)> {
+        events.iter().find_map(|event| match event {
+            GameEvent::Spun { spin, lines, .. } => Some((*spin, *lines)),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn line_clear() {
+        // The I fills the two rows it ends up in, and what is above
+        // them comes down.
+        let mut game = game(
+            "piece: I\n\
+             .........i\n\
+             .........i\n\
+             #########i\n\
+             #########i\n\
+             ##.#######\n",
+        );
+        assert!(game.apply(Action::HardDrop));
+        assert_eq!(cleared(&game.take_events()), Some(2));
+        assert_eq!(game.board_state, BoardState::Clearing);
+        game.fall(Duration::from_secs(0));
+        assert_eq!(game.board_state, BoardState::Moving);
+        assert_eq!(game.lines, 2);
+        assert_eq!(stack(&game), ".........I\n.........I\n##.#######\n");
+    }
+
+    #[test]
+    fn perfect_clear() {
+        let mut game = game(TETRIS);
+        game.apply(Action::HardDrop);
+        game.fall(Duration::from_secs(0));
+        let events = game.take_events();
+        assert_eq!(cleared(&events), Some(4));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, GameEvent::PerfectClear { lines: 4 })));
+        assert_eq!(stack(&game), "..........\n");
+    }
+
+    #[test]
+    fn t_spin_double() {
+        // Turning the T down into the slot, with three of its corners
+        // taken and both in front of it, is a full T-spin.
+        let mut game = game(
+            "piece: T\n\
+             .t########\n\
+             .tt#######\n\
+             #t########\n",
+        );
+        assert!(game.apply(Action::Rotate));
+        assert_eq!(game.piece.rotation, 2);
+        assert!(game.apply(Action::HardDrop));
+        let events = game.take_events();
+        assert_eq!(cleared(&events), Some(2));
+        assert_eq!(spun(&events), Some((Spin::Full, 2)));
+        game.fall(Duration::from_secs(0));
+        assert_eq!(stack(&game), "..########\n");
+    }
+
+    #[test]
+    fn no_spin_without_a_turn() {
+        // The same slot filled without turning the piece is no spin.
+        let mut game = game(
+            "piece: T\n\
+             ..########\n\
+             ttt#######\n\
+             #t########\n",
+        );
+        assert!(game.apply(Action::HardDrop));
+        let events = game.take_events();
+        assert_eq!(cleared(&events), Some(2));
+        assert_eq!(spun(&events), None);
+    }
+
+    #[test]
+    fn t_spin_mini() {
+        // Three corners of the T are taken but only one of those in
+        // front of it, so turning it in is a mini.
+        let mut game = game(
+            "piece: T\n\
+             #t#.......\n\
+             .tt.......\n\
+             #t........\n",
+        );
+        assert!(game.apply(Action::Rotate));
+        game.apply(Action::HardDrop);
+        let events = game.take_events();
+        assert_eq!(spun(&events), Some((Spin::Mini, 0)));
+    }
+
+    #[test]
+    fn garbage() {
+        // Rows of garbage come in at the bottom with one hole each,
+        // pushing the stack up.
+        let mut game = game("piece: O\n#.########\n");
+        game.add_garbage(2);
+        assert_eq!(game.garbage_left(), 2);
+        let text = stack(&game);
+        let rows: Vec<&str> = text.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], "#.########");
+        for row in &rows[1..] {
+            assert_eq!(row.matches('.').count(), 1);
+        }
+    }
+
+    #[test]
+    fn garbage_waits_for_a_clear() {
+        // Garbage sent while rows are being cleared comes in once they
+        // are gone.
+        let mut game = game(TETRIS);
+        game.apply(Action::HardDrop);
+        game.add_garbage(1);
+        assert_eq!(game.garbage_left(), 0);
+        game.fall(Duration::from_secs(0));
+        game.advance(Duration::from_secs(0));
+        assert_eq!(game.garbage_left(), 1);
+        assert_eq!(stack(&game).lines().count(), 1);
+    }
+
+    #[test]
+    fn splits_once_for_each_ten_lines() {
+        // Going from 8 lines to 12 passes 10, at the time of the lock
+        let rules = Rules {
+            line_goal: Some(40),
+            ..Rules::default()
+        };
+        let mut game = game_with(rules, TETRIS);
+        game.lines = 8;
+        game.advance(Duration::from_secs(3));
+        game.apply(Action::HardDrop);
+        game.advance(Duration::from_millis(400));
+        game.fall(Duration::from_secs(0));
+        assert_eq!(
+            splits(&game.take_events()),
+            vec![(10, Duration::from_secs(3))]
+        );
+
+        // From 12 to 16 passes nothing
+        let mut game = game_with(rules, TETRIS);
+        game.lines = 12;
+        game.apply(Action::HardDrop);
+        game.fall(Duration::from_secs(0));
+        assert_eq!(splits(&game.take_events()), vec![]);
+    }
+
+    #[test]
+    fn race_clock_stops_at_the_lock() {
+        // The clear that reaches the goal finishes the race at the
+        // time the piece locked, not once the rows are gone.
+        let rules = Rules {
+            line_goal: Some(20),
+            ..Rules::default()
+        };
+        let mut game = game_with(rules, TETRIS);
+        game.lines = 18;
+        game.advance(Duration::from_secs(30));
+        game.apply(Action::HardDrop);
+        game.advance(Duration::from_millis(400));
+        assert_eq!(game.elapsed, Duration::from_millis(30_400));
+        game.fall(Duration::from_secs(0));
+        let events = game.take_events();
+        assert_eq!(splits(&events), vec![(20, Duration::from_secs(30))]);
+        assert!(events.iter().any(|event| matches!(
+            event,
+            GameEvent::Finished { time, lines: 22 } if *time == Duration::from_secs(30)
+        )));
+        assert_eq!(game.elapsed, Duration::from_secs(30));
+        assert_eq!(game.board_state, BoardState::Over);
+
+        // The clock doesn't run on after the finish
+        game.advance(Duration::from_secs(1));
+        assert_eq!(game.elapsed, Duration::from_secs(30));
+    }
+
+    // The I stands in a well one row deep.
+    const SINGLE: &str = "piece: I\n\
+                          .........i\n\
+                          .........i\n\
+                          .........i\n\
+                          #########i\n";
+
+    const TSD: &str = "piece: T\n\
+                       .t########\n\
+                       .tt#######\n\
+                       #t########\n";
+
+    #[test]
+    fn combo_runs_while_pieces_clear() {
+        let mut game = game(SINGLE);
+        let events = lock(&mut game);
+        assert_eq!(cleared(&events), Some(1));
+        assert_eq!(combo(&events), None);
+
+        lay_out(&mut game, SINGLE);
+        assert_eq!(combo(&lock(&mut game)), Some(1));
+        lay_out(&mut game, SINGLE);
+        assert_eq!(combo(&lock(&mut game)), Some(2));
+
+        // A piece that clears nothing ends it
+        lay_out(&mut game, "piece: O\n....oo....\n....oo....\n");
+        let events = lock(&mut game);
+        assert_eq!(cleared(&events), None);
+        assert_eq!(game.combo, 0);
+
+        lay_out(&mut game, SINGLE);
+        assert_eq!(combo(&lock(&mut game)), None);
+        assert_eq!(game.combo, 1);
+    }
+
+    #[test]
+    fn back_to_back_across_tetrises_and_spins() {
+        let mut game = game(TETRIS);
+        assert_eq!(back_to_back(&lock(&mut game)), None);
+
+        // A T-spin double after a tetris keeps it going
+        lay_out(&mut game, TSD);
+        assert!(game.apply(Action::Rotate));
+        let events = lock(&mut game);
+        assert_eq!(spun(&events), Some((Spin::Full, 2)));
+        assert_eq!(back_to_back(&events), Some(1));
+
+        lay_out(&mut game, TETRIS);
+        assert_eq!(back_to_back(&lock(&mut game)), Some(2));
+
+        // A single breaks it, so the next tetris starts again
+        lay_out(&mut game, SINGLE);
+        assert_eq!(back_to_back(&lock(&mut game)), None);
+        assert_eq!(game.back_to_back, 0);
+        lay_out(&mut game, TETRIS);
+        assert_eq!(back_to_back(&lock(&mut game)), None);
+        assert_eq!(game.back_to_back, 1);
+    }
+
+    #[test]
+    fn first_piece_can_be_blocked() {
+        // A set that brings its piece in over the left wall ends the
+        // game before it starts.
+        let path = std::env::temp_dir().join(format!("ttet-blocked-{}.json", std::process::id()));
+        let text = r#"{"name": "Walled", "pieces": [{"name": "I",
+            "rotations": [["xxxx"]], "spawn": {"x": 0, "y": 20}}]}"#;
+        std::fs::write(&path, text).unwrap();
+        let pieces = PieceSet::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut game = Game::new(1, Rules::default(), default_scoring(), pieces);
+        assert_eq!(game.board_state, BoardState::Over);
+        let events = game.take_events();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            GameEvent::GameOver {
+                reason: GameOverReason::BlockedSpawn,
+                ..
+            }
+        )));
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, GameEvent::PieceSpawned { .. })));
+    }
+
+    fn game_over(events: &[GameEvent]) -> Option<GameOverReason> {
+        events.iter().find_map(|event| match event {
+            GameEvent::GameOver { reason, .. } => Some(*reason),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn lock_out() {
+        // The stack fills the twenty rows shown, so the O locks with
+        // none of it showing.
+        let text = format!(
+            "piece: O\n....oo....\n....oo....\n{}",
+            "#########.\n".repeat(20)
+        );
+        let mut game = game(&text);
+        let events = lock(&mut game);
+        assert_eq!(cleared(&events), None);
+        assert_eq!(game_over(&events), Some(GameOverReason::LockedAbove));
+        assert_eq!(game.board_state, BoardState::Over);
+    }
+
+    #[test]
+    fn pushed_off() {
+        let text = format!("piece: O\n{}", "#########.\n".repeat(10));
+        let mut game = game(&text);
+
+        // Twenty rows take the stack up into the hidden rows, lifting
+        // the piece out of its way.
+        game.add_garbage(20);
+        let events = game.take_events();
+        assert_eq!(game_over(&events), None);
+        assert_eq!(game.board_state, BoardState::Moving);
+        assert!(validate_move(&game.board, game.piece));
+
+        // Any more and the top of it goes off the board
+        game.add_garbage(11);
+        let events = game.take_events();
+        assert_eq!(game_over(&events), Some(GameOverReason::PushedOff));
+        assert_eq!(game.board_state, BoardState::Over);
+    }
+}
@@ -0,0 +1,253 @@
+use std::env;
+use std::time::Duration;
+
+use crate::engine::Piece;
+use crate::pieces::PieceType;
+use crate::spin::Spin;
+
+// Which way a piece moved.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Direction {
+    Left,
+    Right,
+    Down,
+}
+
+// The kind of line clear, by the number of lines cleared at once.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ClearKind {
+    Single,
+    Double,
+    Triple,
+    Tetris,
+}
+
+impl ClearKind {
+    pub fn from_lines(lines: u32) -> ClearKind {
+        match lines {
+            1 => ClearKind::Single,
+            2 => ClearKind::Double,
+            3 => ClearKind::Triple,
+            _ => ClearKind::Tetris,
+        }
+    }
+}
+
+// How a game was lost.  The names are the guideline's block out,
+// lock out and top out.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GameOverReason {
+    // The next piece had no room to come in.
+    BlockedSpawn,
+    // A piece locked without any of it reaching the rows shown.
+    LockedAbove,
+    // Garbage pushed the stack up past the top of the hidden rows.
+    PushedOff,
+}
+
+impl GameOverReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            GameOverReason::BlockedSpawn => "Block out",
+            GameOverReason::LockedAbove => "Lock out",
+            GameOverReason::PushedOff => "Top out",
+        }
+    }
+}
+
+// Everything that happens during game play that the rest of the
+// program may want to react to.  The engine records these as it goes
+// and they are handed to every subscriber once the engine is done with
+// its work for the frame.  Drawing, sound, statistics and logging all
+// work from these events rather than looking into the engine.
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    // A new piece has come in at the top of the board.
+    PieceSpawned {
+        piece: Piece,
+        next: PieceType,
+    },
+    // The piece moved one square.
+    Moved {
+        piece: Piece,
+        direction: Direction,
+    },
+    // The piece turned.
+    Rotated {
+        piece: Piece,
+    },
+    // The piece was dropped all the way down in one move.
+    HardDrop {
+        from: Piece,
+        to: Piece,
+    },
+    // The piece has come to rest and become part of the base.
+    Locked {
+        piece: Piece,
+    },
+    // These board rows were filled and are about to be removed.
+    LinesCleared {
+        count: u32,
+        kind: ClearKind,
+        rows: Vec<usize>,
+    },
+    // The piece that just locked was spun into place, clearing this
+    // many lines.
+    Spun {
+        piece: PieceType,
+        // What the piece is called, and whether it is the T, spun by
+        // the three corner rule.
+        name: String,
+        t_spin: bool,
+        spin: Spin,
+        lines: u32,
+    },
+    // This many pieces in a row before this one cleared lines.
+    Combo {
+        count: u32,
+    },
+    // This many difficult clears in a row before this one.
+    BackToBack {
+        count: u32,
+    },
+    // The lines cleared left the board completely empty.
+    PerfectClear {
+        lines: u32,
+    },
+    // Points were added to the score.
+    Scored {
+        points: u32,
+        total: u32,
+    },
+    // Enough lines have been cleared to move up to this level.
+    LevelUp {
+        level: u32,
+    },
+    // The current piece was put on hold.
+    Hold {
+        held: PieceType,
+    },
+    // The last piece was taken back, and comes in again as this.
+    Undone {
+        piece: Piece,
+    },
+    Paused,
+    Resumed,
+    // Rows of garbage came in from the bottom, pushing the stack up.
+    GarbageAdded {
+        rows: u32,
+    },
+    // Another ten lines towards the line goal were cleared, this long
+    // into the game.
+    Split {
+        lines: u32,
+        time: Duration,
+    },
+    // The line goal was reached or the time limit ran out.
+    Finished {
+        time: Duration,
+        lines: u32,
+    },
+    // There was no room for the next piece, and the board was emptied
+    // to carry on.
+    BoardCleared,
+    // The game was lost, for this reason.
+    GameOver {
+        reason: GameOverReason,
+        score: u32,
+        level: u32,
+        lines: u32,
+    },
+}
+
+// Anything that wants to hear about game events.  The time is how long
+// the program has been running, for anything that animates.
+pub trait Subscriber {
+    fn notify(&mut self, event: &GameEvent, now: Duration);
+}
+
+// Print events to the console as they happen, for debugging.  This is
+// only on when the TTET_LOG environment variable is set, and moves are
+// left out as there are far too many of them to be useful, unless it
+// is set to "all".
+#[derive(Debug, Default)]
+pub struct Logger {
+    moves: bool,
+}
+
+impl Logger {
+    pub fn from_env() -> Option<Logger> {
+        let value = env::var("TTET_LOG").ok()?;
+        Some(Logger {
+            moves: value == "all",
+        })
+    }
+}
+
+// Where a piece is, short enough for a line of the log.
+fn at(piece: &Piece) -> String {
+    format!(
+        "piece {} at {},{} turned {}",
+        piece.tet_type.0, piece.x, piece.y, piece.rotation
+    )
+}
+
+impl Subscriber for Logger {
+    fn notify(&mut self, event: &GameEvent, _now: Duration) {
+        let line = match event {
+            GameEvent::PieceSpawned { piece, next } => {
+                format!("Spawned {}, next {}", at(piece), next.0)
+            }
+            GameEvent::Moved { piece, direction } if self.moves => {
+                format!("Moved {:?} to {}", direction, at(piece))
+            }
+            GameEvent::Rotated { piece } if self.moves => format!("Rotated to {}", at(piece)),
+            GameEvent::Moved { .. } | GameEvent::Rotated { .. } => return,
+            GameEvent::HardDrop { from, to } => {
+                format!("Hard drop from {},{} to {}", from.x, from.y, at(to))
+            }
+            GameEvent::Locked { piece } => format!("Locked {}", at(piece)),
+            GameEvent::LinesCleared { count, kind, rows } => {
+                format!("Cleared {} lines ({:?}), rows {:?}", count, kind, rows)
+            }
+            GameEvent::Spun {
+                piece,
+                name,
+                t_spin,
+                spin,
+                lines,
+            } => format!(
+                "Spun piece {} ({}, T-spin {}) {:?} for {} lines",
+                piece.0, name, t_spin, spin, lines
+            ),
+            GameEvent::Combo { count } => format!("Combo {}", count),
+            GameEvent::BackToBack { count } => format!("Back to back {}", count),
+            GameEvent::PerfectClear { lines } => format!("Perfect clear with {} lines", lines),
+            GameEvent::Scored { points, total } => format!("Scored {}, total {}", points, total),
+            GameEvent::LevelUp { level } => format!("Level {}", level),
+            GameEvent::Hold { held } => format!("Held piece {}", held.0),
+            GameEvent::Undone { piece } => format!("Undone, back to {}", at(piece)),
+            GameEvent::Paused => "Paused".to_string(),
+            GameEvent::Resumed => "Resumed".to_string(),
+            GameEvent::GarbageAdded { rows } => format!("{} rows of garbage", rows),
+            GameEvent::Split { lines, time } => format!("Split at {} lines, {:?}", lines, time),
+            GameEvent::Finished { time, lines } => {
+                format!("Finished with {} lines in {:?}", lines, time)
+            }
+            GameEvent::BoardCleared => "Board cleared".to_string(),
+            GameEvent::GameOver {
+                reason,
+                score,
+                level,
+                lines,
+            } => format!(
+                "Game Over ({}), score {} level {} lines {}",
+                reason.name(),
+                score,
+                level,
+                lines
+            ),
+        };
+        println!("{}", line);
+    }
+}
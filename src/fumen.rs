@@ -0,0 +1,763 @@
+use std::fs;
+
+use crate::board::Size;
+use crate::diagram::Diagram;
+use crate::engine::{moved_onto, tet_points, Board, Piece, TileType};
+use crate::pieces::{PieceSet, PieceType};
+use crate::save::SavedGame;
+
+// Reading and writing fumen, the format positions are shared in on
+// the web.  Only the current version, v115, is understood.  A fumen is
+// a list of pages, each a field 10 wide and 23 high with a row of
+// garbage to come below it, the piece in play and a comment.  Each
+// page's field is written as the changes from the page before, run
+// length encoded, and everything is packed into base 64.
+//
+// Fumen only knows the tetrominoes, on a board 10 wide.  A bigger
+// board has its edges cut off and a smaller one only takes what fits.
+
+const PREFIX: &str = "v115@";
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// The size of a fumen field, less the garbage row, and every square
+// on a page counting it.
+const WIDTH: usize = 10;
+const ROWS: usize = 23;
+const SQUARES: usize = WIDTH * (ROWS + 1);
+
+// Fumen numbers the pieces in this order, with 0 for an empty square
+// and 8 for grey, which is garbage.
+const EMPTY: u8 = 0;
+const GREY: u8 = 8;
+const NAMES: [&str; 8] = ["", "I", "L", "O", "Z", "T", "J", "S"];
+
+// The squares of each piece around its middle as it spawns, with up
+// as positive.
+const MINOS: [[(i32, i32); 4]; 8] = [
+    [(0, 0); 4],
+    [(0, 0), (-1, 0), (1, 0), (2, 0)],
+    [(0, 0), (-1, 0), (1, 0), (1, 1)],
+    [(0, 0), (1, 0), (0, 1), (1, 1)],
+    [(0, 0), (1, 0), (0, 1), (-1, 1)],
+    [(0, 0), (-1, 0), (1, 0), (0, 1)],
+    [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+    [(0, 0), (-1, 0), (0, 1), (1, 1)],
+];
+
+// Fumen numbers the rotations starting upside down: 0 is turned
+// twice, 1 is turned clockwise, 2 is as it spawns and 3 is turned
+// anticlockwise.  Ours start as it spawns, so swapping 0 and 2 goes
+// either way.
+const ROTATIONS: [u8; 4] = [2, 1, 0, 3];
+
+// Comments are escaped to printable ASCII, each character a digit in
+// base 96 counting from the space, four to a number.
+const COMMENT_BASE: u32 = 96;
+const MAX_COMMENT: usize = 4095;
+
+// A fumen field, rows from the top with the garbage row last.
+type Field = [[u8; WIDTH]; ROWS + 1];
+
+// One page of a fumen, on our board.
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub board: Board,
+    // The piece in play, if there is one the set we are playing with
+    // has too.
+    pub piece: Option<Piece>,
+    pub comment: Option<String>,
+}
+
+// A piece as fumen has it, with its middle counted in columns from
+// the left and rows up from the bottom of the field.
+#[derive(Debug, Clone, Copy)]
+struct FumenPiece {
+    kind: u8,
+    rotation: u8,
+    x: i32,
+    y: i32,
+}
+
+impl FumenPiece {
+    fn squares(self) -> Vec<(i32, i32)> {
+        MINOS[self.kind as usize]
+            .iter()
+            .map(|&(dx, dy)| {
+                let (dx, dy) = match self.rotation {
+                    0 => (-dx, -dy),
+                    1 => (dy, -dx),
+                    3 => (-dy, dx),
+                    _ => (dx, dy),
+                };
+                (self.x + dx, self.y + dy)
+            })
+            .collect()
+    }
+}
+
+// Fumen keeps a few pieces one square away from their middle in some
+// rotations.  This is how far to move them to get it.
+fn nudge(kind: u8, rotation: u8) -> (i32, i32) {
+    match (NAMES[kind as usize], rotation) {
+        ("O", 3) => (1, -1),
+        ("O", 0) | ("I", 0) | ("Z", 3) => (1, 0),
+        ("O", 2) | ("I", 3) | ("S", 2) | ("Z", 2) => (0, -1),
+        ("S", 1) => (-1, 0),
+        _ => (0, 0),
+    }
+}
+
+// What happens on a page besides the field.
+struct Action {
+    piece: Option<FumenPiece>,
+    // Raise the garbage row into the field after this page.
+    rise: bool,
+    // Flip the field after this page.
+    mirror: bool,
+    // Colour the pieces, which only the first page says.
+    colour: bool,
+    comment: bool,
+    // Put the piece into the field, clearing any full rows, before the
+    // next page.
+    lock: bool,
+}
+
+impl Action {
+    fn decode(value: u32) -> Action {
+        let kind = (value % 8) as u8;
+        let value = value / 8;
+        let rotation = (value % 4) as u8;
+        let value = value / 4;
+        let at = value as usize % SQUARES;
+        let flags = value / SQUARES as u32;
+        let (dx, dy) = nudge(kind, rotation);
+        let piece = FumenPiece {
+            kind,
+            rotation,
+            x: (at % WIDTH) as i32 + dx,
+            y: ROWS as i32 - 1 - (at / WIDTH) as i32 + dy,
+        };
+        Action {
+            piece: if kind == EMPTY { None } else { Some(piece) },
+            rise: flags & 1 != 0,
+            mirror: flags & 2 != 0,
+            colour: flags & 4 != 0,
+            comment: flags & 8 != 0,
+            lock: flags & 16 == 0,
+        }
+    }
+
+    fn encode(&self) -> u32 {
+        let (kind, rotation, at) = match self.piece {
+            Some(piece) => {
+                let (dx, dy) = nudge(piece.kind, piece.rotation);
+                let column = piece.x - dx;
+                let row = ROWS as i32 - 1 - (piece.y - dy);
+                (piece.kind, piece.rotation, row * WIDTH as i32 + column)
+            }
+            None => (EMPTY, 0, 0),
+        };
+        let flags = self.rise as u32
+            | (self.mirror as u32) << 1
+            | (self.colour as u32) << 2
+            | (self.comment as u32) << 3
+            | (!self.lock as u32) << 4;
+        ((flags * SQUARES as u32 + at as u32) * 4 + rotation as u32) * 8 + kind as u32
+    }
+}
+
+// The base 64 digits of a fumen, read a number at a time.
+struct Reader {
+    digits: Vec<u32>,
+    at: usize,
+}
+
+impl Reader {
+    // Fumen breaks long lines with '?', which we skip along with any
+    // other whitespace.
+    fn new(data: &str) -> Result<Reader, String> {
+        let digits = data
+            .chars()
+            .filter(|&c| c != '?' && !c.is_whitespace())
+            .map(|c| {
+                BASE64
+                    .iter()
+                    .position(|&b| b as char == c)
+                    .map(|digit| digit as u32)
+                    .ok_or_else(|| format!("'{}' can't be in a fumen", c))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Reader { digits, at: 0 })
+    }
+
+    fn is_done(&self) -> bool {
+        self.at >= self.digits.len()
+    }
+
+    // A number written in this many digits, lowest first.
+    fn read(&mut self, count: usize) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let digit = self.digits.get(self.at).ok_or("the fumen is cut short")?;
+            value += digit * 64u32.pow(i as u32);
+            self.at += 1;
+        }
+        Ok(value)
+    }
+}
+
+fn write(digits: &mut Vec<u32>, value: u32, count: usize) {
+    let mut value = value;
+    for _ in 0..count {
+        digits.push(value % 64);
+        value /= 64;
+    }
+}
+
+// Read the pages of a fumen onto boards of the given size.  Anything
+// before the version, like the address of a fumen site, is skipped.
+pub fn decode(text: &str, size: Size, pieces: &PieceSet) -> Result<Vec<Page>, String> {
+    let start = text.find(PREFIX).ok_or("it isn't a v115 fumen")?;
+    let mut reader = Reader::new(&text[start + PREFIX.len()..])?;
+    let mut pages = Vec::new();
+    let mut field: Field = [[EMPTY; WIDTH]; ROWS + 1];
+    // How many more pages have the same field as the one before.
+    let mut repeats = 0;
+    while !reader.is_done() {
+        if repeats > 0 {
+            repeats -= 1;
+        } else {
+            read_field(&mut reader, &mut field, &mut repeats)?;
+        }
+        let action = Action::decode(reader.read(3)?);
+        let comment = if action.comment {
+            Some(read_comment(&mut reader)?)
+        } else {
+            None
+        };
+        let board = to_board(&field, size, pieces);
+        let piece = action
+            .piece
+            .and_then(|piece| to_piece(piece, &board, pieces));
+        pages.push(Page {
+            board,
+            piece,
+            comment,
+        });
+        lock(&mut field, &action);
+    }
+    if pages.is_empty() {
+        return Err("there are no pages".to_string());
+    }
+    Ok(pages)
+}
+
+// Apply the changes to the field.  A field with no changes is followed
+// by how many more pages have it too.
+fn read_field(reader: &mut Reader, field: &mut Field, repeats: &mut u32) -> Result<(), String> {
+    let mut square = 0;
+    while square < SQUARES {
+        let run = reader.read(2)?;
+        let change = (run / SQUARES as u32) as i32 - 8;
+        let length = run as usize % SQUARES + 1;
+        if change > 8 || square + length > SQUARES {
+            return Err("the field doesn't make sense".to_string());
+        }
+        if change == 0 && length == SQUARES {
+            *repeats = reader.read(1)?;
+        }
+        for _ in 0..length {
+            let kind = &mut field[square / WIDTH][square % WIDTH];
+            let changed = *kind as i32 + change;
+            if changed < EMPTY as i32 || changed > GREY as i32 {
+                return Err("the field doesn't make sense".to_string());
+            }
+            *kind = changed as u8;
+            square += 1;
+        }
+    }
+    Ok(())
+}
+
+fn write_field(digits: &mut Vec<u32>, last: &Field, field: &Field) {
+    let changes: Vec<u32> = field
+        .iter()
+        .flatten()
+        .zip(last.iter().flatten())
+        .map(|(&now, &before)| now as u32 + 8 - before as u32)
+        .collect();
+    let mut start = 0;
+    while start < SQUARES {
+        let change = changes[start];
+        let length = changes[start..]
+            .iter()
+            .take_while(|&&c| c == change)
+            .count();
+        write(digits, change * SQUARES as u32 + length as u32 - 1, 2);
+        start += length;
+    }
+}
+
+// usize::div_ceil is newer than the oldest Rust the game builds with.
+#[allow(clippy::manual_div_ceil)]
+fn read_comment(reader: &mut Reader) -> Result<String, String> {
+    let length = reader.read(2)? as usize;
+    let mut escaped = String::new();
+    for _ in 0..(length + 3) / 4 {
+        let mut value = reader.read(5)?;
+        for _ in 0..4 {
+            escaped.push((b' ' + (value % COMMENT_BASE) as u8) as char);
+            value /= COMMENT_BASE;
+        }
+    }
+    escaped.truncate(length);
+    Ok(unescape(&escaped))
+}
+
+fn write_comment(digits: &mut Vec<u32>, comment: &str) {
+    let mut escaped = escape(comment);
+    escaped.truncate(MAX_COMMENT);
+    write(digits, escaped.len() as u32, 2);
+    for chunk in escaped.as_bytes().chunks(4) {
+        let value = chunk
+            .iter()
+            .rev()
+            .fold(0, |value, &c| value * COMMENT_BASE + u32::from(c - b' '));
+        write(digits, value, 5);
+    }
+}
+
+// Comments are escaped the way JavaScript's escape() does it, which is
+// where fumen comes from.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for unit in text.encode_utf16() {
+        match std::char::from_u32(u32::from(unit)) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => escaped.push(c),
+            _ if unit < 256 => escaped.push_str(&format!("%{:02X}", unit)),
+            _ => escaped.push_str(&format!("%u{:04X}", unit)),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let hex = |from: usize, count: usize| {
+        let digits = chars.get(from..from + count)?;
+        if !digits.iter().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        u16::from_str_radix(&digits.iter().collect::<String>(), 16).ok()
+    };
+    let mut units = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let escaped = match (chars[i], chars.get(i + 1)) {
+            ('%', Some('u')) => hex(i + 2, 4).map(|unit| (unit, 6)),
+            ('%', _) => hex(i + 1, 2).map(|unit| (unit, 3)),
+            _ => None,
+        };
+        match escaped {
+            Some((unit, length)) => {
+                units.push(unit);
+                i += length;
+            }
+            None => {
+                units.push(chars[i] as u16);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+// What the field is for the next page: the piece put in, full rows
+// cleared, then the garbage raised and the field flipped if asked.
+fn lock(field: &mut Field, action: &Action) {
+    if !action.lock {
+        return;
+    }
+    if let Some(piece) = action.piece {
+        for (x, y) in piece.squares() {
+            if (0..WIDTH as i32).contains(&x) && (0..ROWS as i32).contains(&y) {
+                field[ROWS - 1 - y as usize][x as usize] = piece.kind;
+            }
+        }
+    }
+    let kept: Vec<[u8; WIDTH]> = field[..ROWS]
+        .iter()
+        .filter(|row| row.contains(&EMPTY))
+        .copied()
+        .collect();
+    let cleared = ROWS - kept.len();
+    for (row, squares) in field[..ROWS].iter_mut().enumerate() {
+        *squares = if row < cleared {
+            [EMPTY; WIDTH]
+        } else {
+            kept[row - cleared]
+        };
+    }
+    if action.rise {
+        field.copy_within(1.., 0);
+        field[ROWS] = [EMPTY; WIDTH];
+    }
+    if action.mirror {
+        for row in field[..ROWS].iter_mut() {
+            row.reverse();
+        }
+    }
+}
+
+// The board array row for each field row, the bottom of the field
+// sitting on our floor.  Field rows too high for the board have none.
+fn board_row(board: &Board, row: usize) -> Option<usize> {
+    let floor = board.height() - 2;
+    floor.checked_sub(ROWS - row)
+}
+
+// The fumen piece that is this piece of the set, if it is one.
+fn fumen_kind(tet_type: PieceType, pieces: &PieceSet) -> Option<u8> {
+    NAMES
+        .iter()
+        .position(|&name| !name.is_empty() && pieces.tetromino(name) == Some(tet_type))
+        .map(|kind| kind as u8)
+}
+
+fn to_board(field: &Field, size: Size, pieces: &PieceSet) -> Board {
+    let mut board = Board::playfield(size);
+    for (row, squares) in field[..ROWS].iter().enumerate() {
+        if let Some(y) = board_row(&board, row) {
+            for (x, &kind) in squares.iter().enumerate().take(size.width) {
+                if kind == EMPTY {
+                    continue;
+                }
+                let tile = NAMES
+                    .get(kind as usize)
+                    .and_then(|&name| pieces.tetromino(name))
+                    .map_or(TileType::Garbage, TileType::Piece);
+                board.set(x + 2, y, tile);
+            }
+        }
+    }
+    board
+}
+
+fn to_field(board: &Board, pieces: &PieceSet) -> Field {
+    let mut field = [[EMPTY; WIDTH]; ROWS + 1];
+    for (row, squares) in field[..ROWS].iter_mut().enumerate() {
+        if let Some(y) = board_row(board, row) {
+            for (x, kind) in squares.iter_mut().enumerate() {
+                if x + 2 >= board.width() - 2 {
+                    break;
+                }
+                *kind = match board.get(x + 2, y) {
+                    TileType::Piece(t) => fumen_kind(t, pieces).unwrap_or(GREY),
+                    tile if tile.is_filled() => GREY,
+                    _ => EMPTY,
+                };
+            }
+        }
+    }
+    field
+}
+
+// How far the squares of one shape have to move to be the squares of
+// the other, if they are the same shape.
+fn offset(from: &[(i32, i32)], to: &[(i32, i32)]) -> Option<(i32, i32)> {
+    if from.is_empty() || from.len() != to.len() {
+        return None;
+    }
+    let mut from = from.to_vec();
+    let mut to = to.to_vec();
+    from.sort_by_key(|&(x, y)| (y, x));
+    to.sort_by_key(|&(x, y)| (y, x));
+    let (dx, dy) = (to[0].0 - from[0].0, to[0].1 - from[0].1);
+    if from
+        .iter()
+        .zip(to.iter())
+        .all(|(&(fx, fy), &(tx, ty))| fx + dx == tx && fy + dy == ty)
+    {
+        Some((dx, dy))
+    } else {
+        None
+    }
+}
+
+// Our piece covering the same squares as fumen's, if the set has it
+// and it fits on the board.
+fn to_piece(fumen: FumenPiece, board: &Board, pieces: &PieceSet) -> Option<Piece> {
+    let tet_type = pieces.tetromino(NAMES[fumen.kind as usize])?;
+    let floor = board.height() as i32 - 2;
+    let mut squares = Vec::new();
+    for (x, y) in fumen.squares() {
+        let (x, y) = (x + 2, floor - 1 - y);
+        if x < 0 || y < 0 {
+            return None;
+        }
+        squares.push((x as usize, y as usize));
+    }
+    let piece = Piece {
+        rotation: ROTATIONS[fumen.rotation as usize],
+        ..pieces.spawn(tet_type)
+    };
+    moved_onto(piece, &squares)
+}
+
+// Fumen's piece covering the same squares as ours, if it is a
+// tetromino and all of it is in the field.
+fn to_fumen_piece(piece: Piece, board: &Board, pieces: &PieceSet) -> Option<FumenPiece> {
+    let kind = fumen_kind(piece.tet_type, pieces)?;
+    let floor = board.height() as i32 - 2;
+    let squares: Vec<(i32, i32)> = tet_points(piece)
+        .iter()
+        .map(|&(x, y)| (x as i32 - 2, floor - 1 - y as i32))
+        .collect();
+    if !squares
+        .iter()
+        .all(|&(x, y)| (0..WIDTH as i32).contains(&x) && (0..ROWS as i32).contains(&y))
+    {
+        return None;
+    }
+    let mut fumen = FumenPiece {
+        kind,
+        rotation: ROTATIONS[piece.rotation as usize % 4],
+        x: 0,
+        y: 0,
+    };
+    let (x, y) = offset(&fumen.squares(), &squares)?;
+    fumen.x = x;
+    fumen.y = y;
+    Some(fumen)
+}
+
+// Write the pages out as a fumen.  Each piece is locked in place for
+// the page after, the way the fumen editor does it, which only makes
+// a difference to how the next field is written.
+pub fn encode(pages: &[Page], pieces: &PieceSet) -> String {
+    let mut digits = Vec::new();
+    let mut last: Field = [[EMPTY; WIDTH]; ROWS + 1];
+    // Where the count of pages with an unchanged field is, while there
+    // is room to count another.
+    let mut repeats: Option<usize> = None;
+    for (i, page) in pages.iter().enumerate() {
+        let field = to_field(&page.board, pieces);
+        match repeats {
+            Some(at) if field == last && digits[at] < 63 => digits[at] += 1,
+            _ => {
+                write_field(&mut digits, &last, &field);
+                repeats = if field == last {
+                    digits.push(0);
+                    Some(digits.len() - 1)
+                } else {
+                    None
+                };
+            }
+        }
+        let action = Action {
+            piece: page
+                .piece
+                .and_then(|piece| to_fumen_piece(piece, &page.board, pieces)),
+            rise: false,
+            mirror: false,
+            colour: i == 0,
+            comment: page.comment.is_some(),
+            lock: true,
+        };
+        write(&mut digits, action.encode(), 3);
+        if let Some(comment) = &page.comment {
+            write_comment(&mut digits, comment);
+        }
+        last = field;
+        lock(&mut last, &action);
+    }
+    let mut text = PREFIX.to_string();
+    text.extend(digits.iter().map(|&digit| BASE64[digit as usize] as char));
+    text
+}
+
+// `ttet fumen decode <fumen>` shows each page of a fumen, and
+// `ttet fumen encode <save file>...` writes out the positions in saved
+// games, a page for each.
+pub fn command(args: &[String]) {
+    let pieces = PieceSet::default();
+    match args {
+        [verb, data] if verb == "decode" => match decode(data, Size::default(), &pieces) {
+            Ok(pages) => {
+                for (i, page) in pages.iter().enumerate() {
+                    println!("Page {}", i + 1);
+                    if let Some(comment) = &page.comment {
+                        println!("{}", comment);
+                    }
+                    let diagram = Diagram {
+                        board: page.board.clone(),
+                        piece: page.piece,
+                        held: None,
+                        queue: Vec::new(),
+                    };
+                    print!("{}", diagram.to_text(&pieces));
+                }
+            }
+            Err(e) => println!("Unable to read the fumen: {}", e),
+        },
+        [verb, paths @ ..] if verb == "encode" && !paths.is_empty() => {
+            let mut pages = Vec::new();
+            let mut set = None;
+            for path in paths {
+                let saved = fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| SavedGame::from_text(&text));
+                match saved {
+                    Ok(saved) => {
+                        pages.push(Page {
+                            board: saved.game.board,
+                            piece: Some(saved.game.piece),
+                            comment: None,
+                        });
+                        set.get_or_insert(saved.game.pieces);
+                    }
+                    Err(e) => return println!("Unable to read {}: {}", path, e),
+                }
+            }
+            println!("{}", encode(&pages, &set.unwrap_or(pieces)));
+        }
+        _ => println!("Use: ttet fumen decode <fumen> | ttet fumen encode <save file>..."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Action, Game, Rules};
+    use crate::event::GameEvent;
+    use crate::scoring::Scoring;
+
+    fn empty_page() -> Page {
+        Page {
+            board: Board::playfield(Size::default()),
+            piece: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn empty_field() {
+        // All 240 squares in one run of no change, no repeats and no
+        // piece
+        let pieces = PieceSet::default();
+        assert_eq!(encode(&[empty_page()], &pieces), "v115@vhAAgH");
+        let pages = decode("v115@vhAAgH", Size::default(), &pieces).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].board, Board::playfield(Size::default()));
+        assert!(pages[0].piece.is_none());
+        assert!(pages[0].comment.is_none());
+    }
+
+    #[test]
+    fn garbage_row() {
+        // A run of 220 unchanged squares, nine grey ones along the
+        // bottom row and the last eleven unchanged
+        let pieces = PieceSet::default();
+        let text = "http://fumen.zui.jp/?v115@bhI8KeAgH";
+        let pages = decode(text, Size::default(), &pieces).unwrap();
+        let board = &pages[0].board;
+        let bottom = board.height() - 3;
+        for x in 0..WIDTH {
+            let tile = if x < 9 {
+                TileType::Garbage
+            } else {
+                TileType::Blank
+            };
+            assert_eq!(board.get(x + 2, bottom), tile);
+        }
+        assert!(board.is_empty(bottom - 1));
+        assert_eq!(encode(&pages, &pieces), "v115@bhI8KeAgH");
+    }
+
+    #[test]
+    fn repeated_fields() {
+        // The pages after the first with the same field are counted in
+        // one digit, so after 63 of them the field is written again
+        let pieces = PieceSet::default();
+        let text = encode(&vec![empty_page(); 70], &pieces);
+        let expected = format!("v115@vh/AgH{}vhF{}", "AAA".repeat(63), "AAA".repeat(6));
+        assert_eq!(text, expected);
+        let pages = decode(&text, Size::default(), &pieces).unwrap();
+        assert_eq!(pages.len(), 70);
+    }
+
+    #[test]
+    fn question_marks_are_skipped() {
+        // The fumen site breaks the data with a '?' every 47 characters
+        let pieces = PieceSet::default();
+        let text = encode(&vec![empty_page(); 70], &pieces);
+        let data: Vec<char> = text[PREFIX.len()..].chars().collect();
+        let broken: Vec<String> = data.chunks(47).map(|c| c.iter().collect()).collect();
+        let text = format!("{}{}", PREFIX, broken.join("?"));
+        let pages = decode(&text, Size::default(), &pieces).unwrap();
+        assert_eq!(pages.len(), 70);
+    }
+
+    #[test]
+    fn pieces_and_comments() {
+        let pieces = PieceSet::default();
+        let t = pieces.spawn(pieces.tetromino("T").unwrap());
+        let page = Page {
+            piece: Some(t),
+            comment: Some("T-spin, 100%".to_string()),
+            ..empty_page()
+        };
+        let text = encode(&[page], &pieces);
+        let pages = decode(&text, Size::default(), &pieces).unwrap();
+        let piece = pages[0].piece.unwrap();
+        assert_eq!(
+            (piece.tet_type, piece.x, piece.y, piece.rotation),
+            (t.tet_type, t.x, t.y, t.rotation)
+        );
+        assert_eq!(pages[0].comment.as_deref(), Some("T-spin, 100%"));
+    }
+
+    #[test]
+    fn played_game() {
+        // Pieces locked in a game keep their colours through an export
+        let pieces = PieceSet::default();
+        let scoring = Scoring::Guideline.system(None);
+        let mut game = Game::new(7, Rules::default(), scoring, pieces.clone());
+        let moves = [
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::Rotate,
+            Action::MoveLeft,
+            Action::MoveRight,
+        ];
+        for (i, &action) in moves.iter().enumerate() {
+            for _ in 0..i {
+                game.apply(action);
+            }
+            game.apply(Action::HardDrop);
+        }
+        assert_eq!(game.lines, 0);
+        let locked: Vec<Piece> = game
+            .take_events()
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::Locked { piece } => Some(*piece),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(locked.len(), moves.len());
+        for piece in locked.iter() {
+            for (x, y) in tet_points(*piece) {
+                assert_eq!(game.board.get(x, y), TileType::Piece(piece.tet_type));
+            }
+        }
+
+        let page = Page {
+            board: game.board.clone(),
+            ..empty_page()
+        };
+        let text = encode(&[page], &pieces);
+        let pages = decode(&text, Size::default(), &pieces).unwrap();
+        assert_eq!(pages[0].board, game.board);
+    }
+}
@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+
+// How fast pieces fall at each level.
+//
+// Speeds are in rows per frame, where a frame is a sixtieth of a second
+// as on the consoles the curves come from.  A speed below 1 means the
+// piece takes several frames to fall a row, 1/60 is a row a second.
+// The engine turns this into real time, so the speed doesn't depend on
+// how fast we draw.  20 rows a frame, "20G", takes a piece straight to
+// the bottom.
+
+// The frames in a second, for converting speeds to real time.
+pub const FRAMES_PER_SECOND: f32 = 60.0;
+
+// The fastest anything falls, all the way down the board in one frame.
+pub const MAX_ROWS_PER_FRAME: f32 = 20.0;
+
+// The guideline curve stops at its level 20, our 19.  Past there the
+// formula goes to zero and then negative.
+const GUIDELINE_TOP_LEVEL: u32 = 19;
+
+// Frames for each row on the NES, for levels 0 to 28.  From 29 on it
+// is one row every frame.
+const NES_FRAMES: [u32; 29] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+
+// Tetris: The Grand Master's speeds, as the level they start at and
+// the speed in 256ths of a row per frame.  Its levels go up to 999, so
+// we use the lines cleared for the level.
+const TGM_SPEEDS: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Gravity {
+    // How this game has always been: level + 1 rows a second.
+    Classic,
+    // The original NES speeds.
+    Nes,
+    // The modern guideline curve, reaching 20G at its level 19, our
+    // level 18.
+    Guideline,
+    // The Grand Master's curve, by lines cleared.
+    Tgm,
+    // Always this many rows a frame, whatever the level.
+    Fixed(f32),
+}
+
+// The curves that can be picked in the settings, 20G last.
+pub const CURVES: [Gravity; 5] = [
+    Gravity::Classic,
+    Gravity::Nes,
+    Gravity::Guideline,
+    Gravity::Tgm,
+    Gravity::Fixed(MAX_ROWS_PER_FRAME),
+];
+
+impl Gravity {
+    pub fn name(self) -> String {
+        match self {
+            Gravity::Classic => "Classic".to_string(),
+            Gravity::Nes => "NES".to_string(),
+            Gravity::Guideline => "Guideline".to_string(),
+            Gravity::Tgm => "TGM".to_string(),
+            Gravity::Fixed(rows) if rows >= MAX_ROWS_PER_FRAME => "20G".to_string(),
+            Gravity::Fixed(rows) => format!("{}G", rows),
+        }
+    }
+
+    // The speed at a level, with the lines cleared so far for the
+    // curves that go by lines.
+    pub fn rows_per_frame(self, level: u32, lines: u32) -> f32 {
+        let rows = match self {
+            Gravity::Classic => (level + 1) as f32 / FRAMES_PER_SECOND,
+            Gravity::Nes => match NES_FRAMES.get(level as usize) {
+                Some(&frames) => 1.0 / frames as f32,
+                None => 1.0,
+            },
+            Gravity::Guideline => {
+                // The guideline counts levels from 1, we count from 0.
+                let level = level.min(GUIDELINE_TOP_LEVEL) as f32;
+                let seconds = (0.8 - level * 0.007).powf(level);
+                1.0 / (seconds * FRAMES_PER_SECOND)
+            }
+            Gravity::Tgm => {
+                let speed = TGM_SPEEDS
+                    .iter()
+                    .rev()
+                    .find(|(from, _)| lines >= *from)
+                    .map_or(4, |(_, speed)| *speed);
+                speed as f32 / 256.0
+            }
+            Gravity::Fixed(rows) => rows,
+        };
+        rows.min(MAX_ROWS_PER_FRAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < b * 0.001
+    }
+
+    #[test]
+    fn guideline() {
+        // The guideline's level 1 is our 0, a row a second
+        let speed = |level| Gravity::Guideline.rows_per_frame(level, 0);
+        assert!(close(speed(0), 1.0 / 60.0));
+        // Its level 15 is about 7ms a row
+        assert!(close(speed(14), 1.0 / (0.702f32.powi(14) * 60.0)));
+        assert!(speed(14) > 2.0 && speed(14) < 3.0);
+        // Its level 19 is 20G, and it stays there
+        assert_eq!(speed(18), MAX_ROWS_PER_FRAME);
+        assert_eq!(speed(29), MAX_ROWS_PER_FRAME);
+        assert_eq!(speed(1000), MAX_ROWS_PER_FRAME);
+    }
+
+    #[test]
+    fn nes() {
+        let speed = |level| Gravity::Nes.rows_per_frame(level, 0);
+        assert!(close(speed(0), 1.0 / 48.0));
+        assert!(close(speed(18), 1.0 / 3.0));
+        assert!(close(speed(19), 1.0 / 2.0));
+        assert!(close(speed(28), 1.0 / 2.0));
+        // From 29 on it is a row every frame, never 20G
+        assert_eq!(speed(29), 1.0);
+        assert_eq!(speed(1000), 1.0);
+    }
+
+    #[test]
+    fn tgm() {
+        // By lines, whatever the level
+        let speed = |lines| Gravity::Tgm.rows_per_frame(99, lines);
+        assert_eq!(speed(0), 4.0 / 256.0);
+        assert_eq!(speed(29), 4.0 / 256.0);
+        assert_eq!(speed(30), 6.0 / 256.0);
+        assert_eq!(speed(199), 144.0 / 256.0);
+        // At 200 it drops back to the start
+        assert_eq!(speed(200), 4.0 / 256.0);
+        assert_eq!(speed(251), 1.0);
+        assert_eq!(speed(500), MAX_ROWS_PER_FRAME);
+    }
+
+    #[test]
+    fn fixed_is_capped() {
+        assert_eq!(Gravity::Fixed(0.5).rows_per_frame(20, 200), 0.5);
+        assert_eq!(
+            Gravity::Fixed(50.0).rows_per_frame(0, 0),
+            MAX_ROWS_PER_FRAME
+        );
+        assert_eq!(Gravity::Classic.rows_per_frame(59, 0), 1.0);
+    }
+}
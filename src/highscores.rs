@@ -0,0 +1,349 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+// The local high score tables, one for each way of playing, kept in a
+// file in the user data directory.
+//
+// The file is JSON with a version number.  If it can't be read, or was
+// written by a newer version of the program, it is moved aside (so
+// nothing is lost) and we start again with empty tables rather than
+// refuse to start.
+
+const FILE_NAME: &str = "highscores.json";
+const VERSION: u32 = 1;
+
+// How many entries we keep in each table.
+pub const TABLE_SIZE: usize = 10;
+
+// Whether a bigger or smaller value is better.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Ranking {
+    HighestFirst,
+    LowestFirst,
+}
+
+// One high score table, and how to show and rank its values.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Table {
+    // The key the table is stored under in the file.
+    pub id: &'static str,
+    pub title: &'static str,
+    pub ranking: Ranking,
+    // What the value is: points or milliseconds.
+    pub unit: Unit,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Unit {
+    Points,
+    Millis,
+}
+
+pub const MARATHON: Table = Table {
+    id: "marathon",
+    title: "Marathon",
+    ranking: Ranking::HighestFirst,
+    unit: Unit::Points,
+};
+pub const SPRINT_20: Table = Table {
+    id: "sprint20",
+    title: "Sprint 20",
+    ranking: Ranking::LowestFirst,
+    unit: Unit::Millis,
+};
+pub const SPRINT_40: Table = Table {
+    id: "sprint40",
+    title: "Sprint 40",
+    ranking: Ranking::LowestFirst,
+    unit: Unit::Millis,
+};
+pub const SPRINT_100: Table = Table {
+    id: "sprint100",
+    title: "Sprint 100",
+    ranking: Ranking::LowestFirst,
+    unit: Unit::Millis,
+};
+pub const ULTRA: Table = Table {
+    id: "ultra",
+    title: "Ultra",
+    ranking: Ranking::HighestFirst,
+    unit: Unit::Points,
+};
+pub const DIG: Table = Table {
+    id: "dig",
+    title: "Dig",
+    ranking: Ranking::LowestFirst,
+    unit: Unit::Millis,
+};
+pub const DIG_RISING: Table = Table {
+    id: "dig_rising",
+    title: "Dig rising",
+    ranking: Ranking::LowestFirst,
+    unit: Unit::Millis,
+};
+
+// Every table, in the order they are shown.
+pub const TABLES: [Table; 7] = [
+    MARATHON, SPRINT_20, SPRINT_40, SPRINT_100, ULTRA, DIG, DIG_RISING,
+];
+
+impl Table {
+    // Is a better than b?
+    fn better(&self, a: u64, b: u64) -> bool {
+        match self.ranking {
+            Ranking::HighestFirst => a > b,
+            Ranking::LowestFirst => a < b,
+        }
+    }
+
+    pub fn format_value(&self, value: u64) -> String {
+        match self.unit {
+            Unit::Points => format!("{}", value),
+            Unit::Millis => format_time(value),
+        }
+    }
+}
+
+// A time in milliseconds as minutes, seconds and thousandths.
+pub fn format_time(millis: u64) -> String {
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        (millis / 1000) % 60,
+        millis % 1000
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    // The score, time or speed, depending on the table.
+    pub value: u64,
+    // When the game was played, in seconds since 1970.
+    pub date: u64,
+    // The seed for the piece order, so the game can be played again.
+    pub seed: u64,
+    // The settings the game was played with, for example the goal.
+    pub settings: String,
+    // For sprints, the time in milliseconds at every ten lines.
+    #[serde(default)]
+    pub splits: Vec<u64>,
+}
+
+impl Entry {
+    pub fn new(name: String, value: u64, seed: u64, settings: String) -> Entry {
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Entry {
+            name,
+            value,
+            date,
+            seed,
+            settings,
+            splits: Vec::new(),
+        }
+    }
+
+    // The date as year-month-day.
+    pub fn format_date(&self) -> String {
+        // Days since 1970 to a calendar date, from Howard Hinnant's
+        // civil_from_days.
+        let z = (self.date / 86_400) as i64 + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct HighScoreFile {
+    version: u32,
+    tables: BTreeMap<String, Vec<Entry>>,
+}
+
+#[derive(Debug, Default)]
+pub struct HighScores {
+    tables: BTreeMap<String, Vec<Entry>>,
+    // Where to save, or none to keep them in memory only.
+    path: Option<PathBuf>,
+}
+
+impl HighScores {
+    // Read the tables from the data directory.  This always gives us
+    // some tables, empty if there were none or they couldn't be read.
+    pub fn load(dir: &Path) -> HighScores {
+        let path = dir.join(FILE_NAME);
+        let tables = match fs::read_to_string(&path) {
+            Ok(text) => match serde_json::from_str::<HighScoreFile>(&text) {
+                Ok(file) if file.version <= VERSION => file.tables,
+                Ok(file) => {
+                    println!(
+                        "{} is version {}, newer than we understand",
+                        path.display(),
+                        file.version
+                    );
+                    set_aside(&path);
+                    BTreeMap::new()
+                }
+                Err(e) => {
+                    println!("Unable to read {}: {}", path.display(), e);
+                    set_aside(&path);
+                    BTreeMap::new()
+                }
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                println!("Unable to read {}: {}", path.display(), e);
+                BTreeMap::new()
+            }
+        };
+        HighScores {
+            tables,
+            path: Some(path),
+        }
+    }
+
+    // Write the tables out.  We write to a new file and then move it
+    // into place, so a crash part way through can't leave a broken
+    // file behind.
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = HighScoreFile {
+            version: VERSION,
+            tables: self.tables.clone(),
+        };
+        let text = serde_json::to_string_pretty(&file)?;
+        let temp = path.with_extension("json.new");
+        fs::write(&temp, text)?;
+        fs::rename(&temp, path)
+    }
+
+    pub fn entries(&self, table: Table) -> &[Entry] {
+        self.tables.get(table.id).map_or(&[], |t| t.as_slice())
+    }
+
+    // Would this value make it onto the table?
+    pub fn qualifies(&self, table: Table, value: u64) -> bool {
+        let entries = self.entries(table);
+        entries.len() < TABLE_SIZE || entries.iter().any(|e| table.better(value, e.value))
+    }
+
+    // Add an entry and save the tables.  Returns the place it got,
+    // counting from 0, if it made the table at all.
+    pub fn add(&mut self, table: Table, entry: Entry) -> Option<usize> {
+        let entries = self.tables.entry(table.id.to_string()).or_default();
+        let at = entries
+            .iter()
+            .position(|e| table.better(entry.value, e.value))
+            .unwrap_or(entries.len());
+        if at >= TABLE_SIZE {
+            return None;
+        }
+        entries.insert(at, entry);
+        entries.truncate(TABLE_SIZE);
+
+        if let Err(e) = self.save() {
+            println!("Unable to save high scores: {}", e);
+        }
+        Some(at)
+    }
+}
+
+// Move a file we can't read out of the way, keeping it in case it can
+// be fixed by hand.
+pub fn set_aside(path: &Path) {
+    let aside = path.with_extension("json.bad");
+    match fs::rename(path, &aside) {
+        Ok(()) => println!("Moved it to {}", aside.display()),
+        Err(e) => println!("Unable to move it aside: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    // An empty directory of our own to keep a file in.
+    fn empty_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("ttet-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(name: &str, value: u64) -> Entry {
+        Entry::new(name.to_string(), value, 7, String::new())
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = empty_dir("scores-round-trip");
+        let mut scores = HighScores::load(&dir);
+        assert!(scores.entries(MARATHON).is_empty());
+        assert_eq!(scores.add(MARATHON, entry("low", 100)), Some(0));
+        assert_eq!(scores.add(MARATHON, entry("high", 300)), Some(0));
+        assert_eq!(scores.add(SPRINT_40, entry("fast", 61_500)), Some(0));
+
+        let loaded = HighScores::load(&dir);
+        assert_eq!(loaded.entries(MARATHON), scores.entries(MARATHON));
+        assert_eq!(loaded.entries(MARATHON)[0].name, "high");
+        assert_eq!(loaded.entries(SPRINT_40)[0].value, 61_500);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_file_is_set_aside() {
+        let dir = empty_dir("scores-corrupt");
+        let path = dir.join(FILE_NAME);
+        fs::write(&path, "{ not json").unwrap();
+        let mut scores = HighScores::load(&dir);
+        assert!(scores.entries(MARATHON).is_empty());
+
+        // Saving starts a new file, the old one is kept to one side
+        scores.add(MARATHON, entry("new", 10));
+        let aside = fs::read_to_string(dir.join("highscores.json.bad")).unwrap();
+        assert_eq!(aside, "{ not json");
+        assert_eq!(HighScores::load(&dir).entries(MARATHON).len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let dir = empty_dir("scores-newer");
+        let file = HighScoreFile {
+            version: VERSION + 1,
+            tables: vec![("marathon".to_string(), vec![entry("future", 999)])]
+                .into_iter()
+                .collect(),
+        };
+        let text = serde_json::to_string(&file).unwrap();
+        fs::write(dir.join(FILE_NAME), &text).unwrap();
+        let scores = HighScores::load(&dir);
+        assert!(scores.entries(MARATHON).is_empty());
+        assert!(!dir.join(FILE_NAME).exists());
+        let aside = fs::read_to_string(dir.join("highscores.json.bad")).unwrap();
+        assert_eq!(aside, text);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+use ggez::graphics::Rect;
+
+use crate::board::Size;
+
+// The layout of everything we draw on the screen, computed from the
+// current window size.
+//
+// Everything is measured in "cells", the size of one square on the
+// playfield.  We work out how many cells the whole screen needs, pick
+// the largest cell size that fits in the window, then center the
+// result.  Every draw function takes its positions from here instead
+// of using fixed pixel locations, so the game looks the same at any
+// window size, in fullscreen, and on HiDPI displays (where we lay out
+// in physical pixels).
+
+// The first board array column drawn as the playfield grid.  Column 0
+// and the last column only exist to give the I piece room to rotate,
+// so they are never shown.  The grid is the playfield with its walls
+// and floor, less any hidden rows at the top.
+pub const GRID_FIRST_COLUMN: usize = 1;
+
+// The panels beside the playfield are at least this tall, so a short
+// board still leaves room for the stats.
+const MIN_PANEL_ROWS: usize = 25;
+
+// The size of the grid for the next piece box and the hold box.
+pub const PREVIEW_COLUMNS: usize = 5;
+pub const PREVIEW_ROWS: usize = 4;
+
+// Space around the panels, in cells.
+const MARGIN: f32 = 1.0;
+
+// The panels either side of the playfield.  The hold box and stats go on
+// the left, the next piece box on the right.
+const SIDE_PANEL_COLUMNS: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    // The screen size this is for.
+    pub screen: (f32, f32),
+    // The playfield being laid out.
+    pub size: Size,
+    // The board array rows and columns shown as the grid, and the
+    // first row shown.
+    pub grid_columns: usize,
+    pub grid_rows: usize,
+    pub first_row: usize,
+    // The size of one board square.
+    pub cell: f32,
+    // The playfield grid, including the border columns and the floor.
+    pub playfield: Rect,
+    // Boxes for the held piece and the upcoming piece.
+    pub hold: Rect,
+    pub next: Rect,
+    // Text for score, lines, level and the like.
+    pub stats: Rect,
+    // The rest of the right panel, below the next box, where the
+    // bot's board goes in versus.
+    pub side: Rect,
+}
+
+impl Layout {
+    // Compute the layout of a playfield for a screen of the given
+    // size.  The screen size is in the units we draw with, which are
+    // physical pixels.
+    pub fn new(width: f32, height: f32, size: Size) -> Layout {
+        let grid_columns = size.width + 2;
+        let grid_rows = size.height + 1;
+        let panel_rows = grid_rows.max(MIN_PANEL_ROWS);
+
+        // The total size of the screen in cells.
+        let total_columns = MARGIN
+            + SIDE_PANEL_COLUMNS
+            + MARGIN
+            + grid_columns as f32
+            + MARGIN
+            + SIDE_PANEL_COLUMNS
+            + MARGIN;
+        let total_rows = MARGIN + panel_rows as f32 + MARGIN;
+        let cell = (width / total_columns)
+            .min(height / total_rows)
+            .floor()
+            .max(1.0);
+
+        // Center the cell grid on the screen.
+        let left = ((width - cell * total_columns) / 2.0).floor();
+        let top = ((height - cell * total_rows) / 2.0).floor();
+
+        let hold_x = left + MARGIN * cell;
+        let playfield_x = hold_x + (SIDE_PANEL_COLUMNS + MARGIN) * cell;
+        let next_x = playfield_x + (grid_columns as f32 + MARGIN) * cell;
+        let panel_y = top + MARGIN * cell;
+        let panel_bottom = panel_y + panel_rows as f32 * cell;
+
+        let playfield = Rect::new(
+            playfield_x,
+            panel_y,
+            grid_columns as f32 * cell,
+            grid_rows as f32 * cell,
+        );
+        let hold = Rect::new(
+            hold_x,
+            panel_y + cell,
+            PREVIEW_COLUMNS as f32 * cell,
+            PREVIEW_ROWS as f32 * cell,
+        );
+        let next = Rect::new(
+            next_x,
+            panel_y + cell,
+            PREVIEW_COLUMNS as f32 * cell,
+            PREVIEW_ROWS as f32 * cell,
+        );
+        let stats_y = hold.y + hold.h + MARGIN * cell;
+        let stats = Rect::new(
+            hold_x,
+            stats_y,
+            SIDE_PANEL_COLUMNS * cell,
+            panel_bottom - stats_y,
+        );
+        let side_y = next.y + next.h + MARGIN * cell;
+        let side = Rect::new(
+            next_x,
+            side_y,
+            SIDE_PANEL_COLUMNS * cell,
+            panel_bottom - side_y,
+        );
+
+        Layout {
+            screen: (width, height),
+            size,
+            grid_columns,
+            grid_rows,
+            first_row: size.hidden,
+            cell,
+            playfield,
+            hold,
+            next,
+            stats,
+            side,
+        }
+    }
+
+    // The same playfield drawn as large as fits in a smaller area,
+    // for showing a second board.
+    pub fn inset(&self, area: Rect) -> Layout {
+        let cell = (area.w / self.grid_columns as f32)
+            .min(area.h / self.grid_rows as f32)
+            .floor()
+            .max(1.0);
+        Layout {
+            cell,
+            playfield: Rect::new(
+                area.x,
+                area.y,
+                self.grid_columns as f32 * cell,
+                self.grid_rows as f32 * cell,
+            ),
+            ..*self
+        }
+    }
+
+    // The same screen laid out for a playfield of a different size.
+    pub fn with_size(&self, size: Size) -> Layout {
+        Layout::new(self.screen.0, self.screen.1, size)
+    }
+
+    // The top left corner on the screen of the board array square at
+    // x, y.  Columns left of the grid and hidden rows give negative
+    // offsets, so check shows() first for anything that could be up
+    // there.
+    pub fn board_square(&self, x: usize, y: usize) -> (f32, f32) {
+        (
+            self.playfield.x + (x as f32 - GRID_FIRST_COLUMN as f32) * self.cell,
+            self.playfield.y + (y as f32 - self.first_row as f32) * self.cell,
+        )
+    }
+
+    // The playfield square at a point on the screen, if there is one
+    // there.  Walls, the floor and hidden rows don't count.
+    pub fn board_cell(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        let column = ((x - self.playfield.x) / self.cell).floor() + GRID_FIRST_COLUMN as f32;
+        let row = ((y - self.playfield.y) / self.cell).floor() + self.first_row as f32;
+        if column < 2.0 || row < self.first_row as f32 {
+            return None;
+        }
+        let (column, row) = (column as usize, row as usize);
+        if column >= self.size.width + 2 || row >= self.first_row + self.size.height {
+            return None;
+        }
+        Some((column, row))
+    }
+
+    // The board array row is below the hidden rows.
+    pub fn shows(&self, y: usize) -> bool {
+        y >= self.first_row
+    }
+
+    // The top left corner of a square inside a preview box.
+    pub fn preview_square(&self, area: Rect, x: usize, y: usize) -> (f32, f32) {
+        (area.x + x as f32 * self.cell, area.y + y as f32 * self.cell)
+    }
+
+    // Text is sized relative to the cells so it scales with the board.
+    pub fn font_size(&self) -> f32 {
+        self.cell * 0.9
+    }
+
+    // The distance between lines of HUD text.
+    pub fn line_height(&self) -> f32 {
+        self.cell * 1.1
+    }
+
+    // Width of the grid lines.
+    pub fn line_width(&self) -> f32 {
+        (self.cell / 10.0).max(1.0)
+    }
+}
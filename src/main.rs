@@ -1,703 +1,105 @@
-use ggez::event::{quit, run, EventHandler, KeyCode, KeyMods};
+use ggez::conf::WindowMode;
+use ggez::event::{quit, run, Button, EventHandler, GamepadId, KeyCode, KeyMods, MouseButton};
 use ggez::graphics;
-use ggez::graphics::{Color, DrawParam};
-use ggez::input;
+use ggez::graphics::{Color, Rect, Scale, Text, TextFragment};
 use ggez::nalgebra::Point2;
-use ggez::timer;
 use ggez::{Context, GameResult};
-use queues::*;
-use rand::{
-    distributions::{Distribution, Standard},
-    seq::SliceRandom,
-    Rng,
-};
 
-// The seven different game pieces we use.  Their official name in Tetris
-// is "Tetrominoes".
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum Tetrominoes {
-    I,
-    O,
-    T,
-    J,
-    L,
-    S,
-    Z,
-}
-
-// Pick a random Tetrominoe from our enum
-// Modified from https://stackoverflow.com/questions/48490049
-impl Distribution<Tetrominoes> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Tetrominoes {
-        match rng.gen_range(0, 7) {
-            0 => Tetrominoes::I,
-            1 => Tetrominoes::O,
-            2 => Tetrominoes::T,
-            3 => Tetrominoes::J,
-            4 => Tetrominoes::L,
-            5 => Tetrominoes::S,
-            _ => Tetrominoes::Z,
-        }
-    }
-}
-
-// We use a 2d array (basically) to hold the board state and to know
-// where to draw the pieces, the base (pieces that hit the floor) and
-// the borders of the playing field.
-// The visible board size is 10x20. We have additional space in the
-// array for reasons that involve how we plot the tetrominoes and
-// translate their x,y location when plotting on the board.
-const BOARD_HEIGHT: usize = 26;
-const BOARD_WIDTH: usize = 14;
-
-// Each board square can be one of these choices.
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum TileType {
-    Border,
-    Tet,
-    Base,
-    Blank,
-}
-
-#[derive(Debug, Clone, Copy)]
-struct Piece {
-    tet_type: Tetrominoes,
-    rotation: u8,
-    x: usize,
-    y: usize,
-}
-
-// Board state is used to indicate the different states that the
-// board can be in during game play.  Specific states will change how
-// things are displayed and what movement is allowed.
-//
-// Moving: The normal state of things, the player can rotate
-// pieces, and pieces drop at every interval.
-//
-// Clearing: We freeze the board for one interval during which a
-// completed row is blanked out.  At the end of Clearing state, the
-// pieces above a cleared row fall to fill the empty rows.
-//
-// Paused: The player has requested a pause, no falling
-// or rotation is allowed.  The piece holds at its current location
-// and no bounds checking is performed.
-//
-// Over: The attempt to place a new piece at the top of the board
-// has failed, meaning at least one square is occupied already.
-#[derive(Debug, PartialEq)]
-enum BoardState {
-    Moving,
-    Clearing,
-    Paused,
-    Over,
-}
-
-// Debug function to print the board
-fn print_board(board: [[TileType; BOARD_HEIGHT]; BOARD_WIDTH]) {
-    for y in 0..BOARD_HEIGHT {
-        print!("[{:02}] ", y);
-        for x in 0..BOARD_WIDTH {
-            if board[x][y] == TileType::Base {
-                print!("B");
-            } else if board[x][y] == TileType::Border {
-                print!("#");
-            } else if board[x][y] == TileType::Tet {
-                print!(".");
-            } else {
-                print!(" ");
-            }
-        }
-        println!();
-    }
-}
-
-// Check a vector of points to see if a piece can be placed on
-// those places on the board.  Return true if it is possible,
-// false if it is not possible.
-fn check_points(board: [[TileType; BOARD_HEIGHT]; BOARD_WIDTH], ptc: Vec<(usize, usize)>) -> bool {
-    for pt in ptc.iter() {
-        if board[pt.0][pt.1] == TileType::Base || board[pt.0][pt.1] == TileType::Border {
-            return false;
-        }
-    }
-    true
-}
-
-// Check to see if the piece in the requested location and rotation
-// will fit on the given board.
-fn validate_move(board: [[TileType; BOARD_HEIGHT]; BOARD_WIDTH], piece: Piece) -> bool {
-    let x = piece.x;
-    let y = piece.y;
-
-    // We put the points of the desired position into a vector
-    // then check at the end if all points are valid.
-    let mut ptc: Vec<(usize, usize)> = Vec::with_capacity(4);
-    match piece.tet_type {
-        Tetrominoes::I => match piece.rotation {
-            0 => {
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-                ptc.push((x + 3, y + 1));
-            }
-            1 => {
-                ptc.push((x + 2, y));
-                ptc.push((x + 2, y + 1));
-                ptc.push((x + 2, y + 2));
-                ptc.push((x + 2, y + 3));
-            }
-            2 => {
-                ptc.push((x, y + 2));
-                ptc.push((x + 1, y + 2));
-                ptc.push((x + 2, y + 2));
-                ptc.push((x + 3, y + 2));
-            }
-            _ => {
-                ptc.push((x + 1, y));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 1, y + 2));
-                ptc.push((x + 1, y + 3));
-            }
-        },
-        Tetrominoes::O => {
-            ptc.push((x, y));
-            ptc.push((x + 1, y));
-            ptc.push((x, y + 1));
-            ptc.push((x + 1, y + 1));
-        }
-        Tetrominoes::T => match piece.rotation {
-            0 => {
-                ptc.push((x + 1, y));
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-            }
-            1 => {
-                ptc.push((x + 1, y));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 1, y + 2));
-                ptc.push((x + 2, y + 1));
-            }
-            2 => {
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-                ptc.push((x + 1, y + 2));
-            }
-            _ => {
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 1, y + 2));
-            }
-        },
-        Tetrominoes::J => match piece.rotation {
-            0 => {
-                ptc.push((x, y));
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-            }
-            1 => {
-                ptc.push((x + 1, y));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 1, y + 2));
-                ptc.push((x + 2, y));
-            }
-            2 => {
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-                ptc.push((x + 2, y + 2));
-            }
-            _ => {
-                ptc.push((x, y + 2));
-                ptc.push((x + 1, y));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 1, y + 2));
-            }
-        },
-        Tetrominoes::L => match piece.rotation {
-            0 => {
-                ptc.push((x + 2, y));
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-            }
-            1 => {
-                ptc.push((x + 1, y));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 1, y + 2));
-                ptc.push((x + 2, y + 2));
-            }
-            2 => {
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-                ptc.push((x, y + 2));
-            }
-            _ => {
-                ptc.push((x, y));
-                ptc.push((x + 1, y));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 1, y + 2));
-            }
-        },
-        Tetrominoes::S => match piece.rotation {
-            0 => {
-                ptc.push((x + 1, y));
-                ptc.push((x + 2, y));
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-            }
-            1 => {
-                ptc.push((x + 1, y));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-                ptc.push((x + 2, y + 2));
-            }
-            2 => {
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-                ptc.push((x, y + 2));
-                ptc.push((x + 1, y + 2));
-            }
-            _ => {
-                ptc.push((x, y));
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 1, y + 2));
-            }
-        },
-        Tetrominoes::Z => match piece.rotation {
-            0 => {
-                ptc.push((x, y));
-                ptc.push((x + 1, y));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-            }
-            1 => {
-                ptc.push((x + 2, y));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 2, y + 1));
-                ptc.push((x + 1, y + 2));
-            }
-            2 => {
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x + 1, y + 2));
-                ptc.push((x + 2, y + 2));
-            }
-            _ => {
-                ptc.push((x + 1, y));
-                ptc.push((x, y + 1));
-                ptc.push((x + 1, y + 1));
-                ptc.push((x, y + 2));
-            }
-        },
-    }
-    if check_points(board, ptc) {
-        return true;
-    }
-    false
-}
-
-// On the given board, draw the shape of the Tetrominoe of
-// the given type in the given rotation.
-// The new_type can be any supported tile type, including blank
-fn plot_tet(board: &mut [[TileType; BOARD_HEIGHT]; BOARD_WIDTH], piece: Piece, new_type: TileType) {
-    // We use the piece location and rotation to determine which
-    // squares we need to update.
-    match piece.tet_type {
-        Tetrominoes::I => match piece.rotation {
-            0 => {
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-                board[piece.x + 3][piece.y + 1] = new_type;
-            }
-            1 => {
-                board[piece.x + 2][piece.y] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 2] = new_type;
-                board[piece.x + 2][piece.y + 3] = new_type;
-            }
-            2 => {
-                board[piece.x][piece.y + 2] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-                board[piece.x + 2][piece.y + 2] = new_type;
-                board[piece.x + 3][piece.y + 2] = new_type;
-            }
-            _ => {
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-                board[piece.x + 1][piece.y + 3] = new_type;
-            }
-        },
-        Tetrominoes::O => {
-            board[piece.x][piece.y] = new_type;
-            board[piece.x + 1][piece.y] = new_type;
-            board[piece.x][piece.y + 1] = new_type;
-            board[piece.x + 1][piece.y + 1] = new_type;
-        }
-        Tetrominoes::T => match piece.rotation {
-            0 => {
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-            }
-            1 => {
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-            }
-            2 => {
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-            }
-            _ => {
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-            }
-        },
-        Tetrominoes::J => match piece.rotation {
-            0 => {
-                board[piece.x][piece.y] = new_type;
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-            }
-            1 => {
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-                board[piece.x + 2][piece.y] = new_type;
-            }
-            2 => {
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 2] = new_type;
-            }
-            _ => {
-                board[piece.x][piece.y + 2] = new_type;
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-            }
-        },
-        Tetrominoes::L => match piece.rotation {
-            0 => {
-                board[piece.x + 2][piece.y] = new_type;
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-            }
-            1 => {
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-                board[piece.x + 2][piece.y + 2] = new_type;
-            }
-            2 => {
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-                board[piece.x][piece.y + 2] = new_type;
-            }
-            _ => {
-                board[piece.x][piece.y] = new_type;
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-            }
-        },
-        Tetrominoes::S => match piece.rotation {
-            0 => {
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x + 2][piece.y] = new_type;
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-            }
-            1 => {
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 2] = new_type;
-            }
-            2 => {
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-                board[piece.x][piece.y + 2] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-            }
-            _ => {
-                board[piece.x][piece.y] = new_type;
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-            }
-        },
-        Tetrominoes::Z => match piece.rotation {
-            0 => {
-                board[piece.x][piece.y] = new_type;
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-            }
-            1 => {
-                board[piece.x + 2][piece.y] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 2][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-            }
-            2 => {
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 2] = new_type;
-                board[piece.x + 2][piece.y + 2] = new_type;
-            }
-            _ => {
-                board[piece.x + 1][piece.y] = new_type;
-                board[piece.x][piece.y + 1] = new_type;
-                board[piece.x + 1][piece.y + 1] = new_type;
-                board[piece.x][piece.y + 2] = new_type;
-            }
-        },
-    }
-}
-
-// Call this when the active piece has hit something below it and can move
-// down no further.  We convert the squares the piece contained to the base
-// type, then we check to see if any rows have been filled.
-//
-// A filled row,       return BoardState::Clearing
-// No filled rows,     return BoardState::Moving (but see below)
-//
-// If we don't clear rows, then we generate the next piece at the top of
-// the board.  If we are clearing, then don't generate the next piece.
-//
-// If we are not clearing rows, then it's possible that the placement of
-// the new piece will fail, in that case the tail call will end up
-// returning BoardState::Over
-//
-fn convert_and_check(
-    board: &mut [[TileType; BOARD_HEIGHT]; BOARD_WIDTH],
-    piece: &mut Piece,
-    piece_queue: &mut TetQueue,
-    next_board: &mut [[TileType; BOARD_HEIGHT]; BOARD_WIDTH],
-    next_piece: &mut Piece,
-) -> BoardState {
-    // Redraw the piece as a "base" type
-    plot_tet(board, *piece, TileType::Base);
-
-    // See if there are any "full" rows.
-    for y in (*piece).y..BOARD_HEIGHT - 2 {
-        let mut row_count = 0;
-        for x in 2..BOARD_WIDTH - 2 {
-            if board[x][y] == TileType::Base {
-                row_count += 1;
-            }
-        }
-        if row_count == 10 {
-            // We have at least one full row, go ahead and tell the caller the
-            // new state.  We don't care how many rows are full, one is
-            // enough to know the board state is changing to clearing and
-            // we can return now.
-            return BoardState::Clearing;
-        }
-    }
-
-    // We can go ahead with placing a new piece now, return the
-    // result of this call
-    place_new_piece(board, piece, piece_queue, next_board, next_piece)
-}
-
-// This starts a new piece moving down from the top of the
-// board.  We also check for game over if the new piece has
-// no empty squares to be placed in.
-fn place_new_piece(
-    board: &mut [[TileType; BOARD_HEIGHT]; BOARD_WIDTH],
-    piece: &mut Piece,
-    piece_queue: &mut TetQueue,
-    next_board: &mut [[TileType; BOARD_HEIGHT]; BOARD_WIDTH],
-    next_piece: &mut Piece,
-) -> BoardState {
-    let mut res = BoardState::Moving;
-
-    (*piece).tet_type = piece_queue.next();
-    if (*piece).tet_type == Tetrominoes::I {
-        (*piece).rotation = 1;
-        (*piece).y = 0;
-    } else {
-        (*piece).rotation = 0;
-        (*piece).y = 2;
-    }
-    (*piece).x = 6;
-
-    // Clear the old and and fill the next_board array with the next piece
-    plot_tet(next_board, *next_piece, TileType::Blank);
-    *next_piece = set_next_piece(piece_queue.peek());
-    plot_tet(next_board, *next_piece, TileType::Tet);
-
-    if !validate_move(*board, *piece) {
-        println!("Game Over");
-        res = BoardState::Over;
-    }
-    res
-}
-
-// Try to move tet down.  If it works, then return true.
-// If the move is not valid, then return false.
-fn move_tet_down(board: &mut [[TileType; BOARD_HEIGHT]; BOARD_WIDTH], piece: &mut Piece) -> bool {
-    (*piece).y += 1;
-    if validate_move(*board, *piece) {
-        (*piece).y -= 1;
-        plot_tet(board, *piece, TileType::Blank);
-        (*piece).y += 1;
-        return true;
-    } // else
-    (*piece).y -= 1;
-    false
-}
-
-// Scoring formula, Original NES, based on lines
-// cleared and current level:
-fn get_score(cleared: u32, level: u32) -> u32 {
-    assert!(cleared > 0 && cleared < 5);
-    match cleared {
-        1 => 40 * (level + 1),
-        2 => 100 * (level + 1),
-        3 => 300 * (level + 1),
-        _ => 1200 * (level + 1),
-    }
-}
-
-// This struct keeps track of the coming tetrominoes.
-// We randomly shuffle the seven valid tets, then put them
-// on a queue whenever the queue gets low.
-// This also enables us to print the next tetrominoe.
-// #[derive(Debug, Clone, Copy)]
-#[derive(Debug)]
-struct TetQueue {
-    q: Queue<Tetrominoes>,
-    x: [Tetrominoes; 7],
-    rng: rand::rngs::ThreadRng,
-}
-
-// We use the random bag method for selecting the next tet.
-impl TetQueue {
-    fn new() -> TetQueue {
-        let mut q: Queue<Tetrominoes> = queue![];
-        let mut x = [
-            Tetrominoes::I,
-            Tetrominoes::O,
-            Tetrominoes::T,
-            Tetrominoes::J,
-            Tetrominoes::L,
-            Tetrominoes::S,
-            Tetrominoes::Z,
-        ];
-
-        let mut rng = rand::thread_rng();
-        x.shuffle(&mut rng);
-        for tet in &x {
-            q.add(*tet).unwrap();
-        }
-        TetQueue { q, x, rng }
-    }
-    fn next(&mut self) -> Tetrominoes {
-        if self.q.size() <= 1 {
-            self.x.shuffle(&mut self.rng);
-            for tet in &self.x {
-                self.q.add(*tet).unwrap();
-            }
-        }
-        self.q.remove().unwrap()
-    }
-    fn peek(&mut self) -> Tetrominoes {
-        self.q.peek().unwrap()
-    }
-}
+mod animation;
+mod audio;
+mod board;
+mod bot;
+mod diagram;
+mod editor;
+mod engine;
+mod event;
+mod fumen;
+mod gravity;
+mod highscores;
+mod layout;
+mod menus;
+mod mode;
+mod pieces;
+mod play;
+mod save;
+mod scene;
+mod scoring;
+mod spin;
+mod stats;
+mod versus;
+
+use engine::{Board, TileType};
+use layout::{Layout, PREVIEW_COLUMNS, PREVIEW_ROWS};
+use menus::TitleScene;
+use pieces::PieceType;
+use scene::{Input, SceneStack, Shared};
+
+// The colour the screen is cleared to, which is also the colour of an
+// empty square.
+const BACKGROUND: [f32; 4] = [0.1, 0.2, 0.3, 1.0];
 
 struct MainState {
-    // The main game board
-    board: [[TileType; BOARD_HEIGHT]; BOARD_WIDTH],
-    // The place where we render the next piece
-    next_board: [[TileType; BOARD_HEIGHT]; BOARD_WIDTH],
-    piece: Piece,
-    next_piece: Piece,
-    piece_queue: TetQueue,
-    board_state: BoardState,
-    score: u32,
-    level: u32,
-    lines: u32,
+    scenes: SceneStack,
+    shared: Shared,
 }
 
-fn set_next_piece(next_tet: Tetrominoes) -> Piece {
-    let mut x = 1;
-    let mut y = 1;
-    let mut rotation = 0;
-
-    if next_tet == Tetrominoes::I {
-        x = 0;
-        y = 0;
-        rotation = 1;
-    };
-    let next_piece = Piece {
-        tet_type: next_tet,
-        rotation,
-        x,
-        y,
-    };
-    next_piece
+// Draw one filled square of the given size with its top left corner
+// at x, y.  We leave a small gap around the square so the grid lines
+// show through.
+fn draw_square(
+    mb: &mut graphics::MeshBuilder,
+    (x, y): (f32, f32),
+    size: f32,
+    color: Color,
+) -> GameResult {
+    mb.line(
+        &[
+            Point2::new(x + size / 2.0, y + size * 0.1),
+            Point2::new(x + size / 2.0, y + size * 0.9),
+        ],
+        size * 0.8,
+        color,
+    )?;
+    Ok(())
 }
 
-fn draw_next_board_grid(mb: &mut graphics::MeshBuilder) -> GameResult {
-    // Draw the horizional lines for the next box
-    for y in (40..=120).step_by(20) {
-        let y = y as f32;
+// Draw the grid for one of the small boxes beside the playfield.
+fn draw_preview_grid(mb: &mut graphics::MeshBuilder, layout: &Layout, area: Rect) -> GameResult {
+    // Draw the horizional lines for the box
+    for row in 0..=PREVIEW_ROWS {
+        let y = area.y + row as f32 * layout.cell;
         mb.line(
-            &[Point2::new(500.0, y), Point2::new(600.0, y)],
-            2.0,
+            &[Point2::new(area.x, y), Point2::new(area.x + area.w, y)],
+            layout.line_width(),
             Color::new(0.9, 0.9, 0.9, 4.0),
         )?;
     }
-    // Draw the vertical lines for the next box
-    for x in (500..=600).step_by(20) {
-        let x = x as f32;
+    // Draw the vertical lines for the box
+    for column in 0..=PREVIEW_COLUMNS {
+        let x = area.x + column as f32 * layout.cell;
         mb.line(
-            &[Point2::new(x, 40.0), Point2::new(x, 120.0)],
-            2.0,
+            &[Point2::new(x, area.y), Point2::new(x, area.y + area.h)],
+            layout.line_width(),
             Color::new(0.9, 0.9, 0.9, 4.0),
         )?;
     }
     Ok(())
 }
 
-fn draw_next_board_contents(
+fn draw_preview_contents(
     mb: &mut graphics::MeshBuilder,
-    board: &mut [[TileType; BOARD_HEIGHT]; BOARD_WIDTH],
+    layout: &Layout,
+    area: Rect,
+    board: &Board,
 ) -> GameResult {
-    // Draw content in the next box
-    for (py, y) in (40..120).step_by(20).enumerate() {
-        for (px, x) in (500..580).step_by(20).enumerate() {
-            let x = x as f32;
-            let y = y as f32;
-            if board[px][py] == TileType::Tet {
-                mb.line(
-                    &[
-                        Point2::new(x + 10.0, y + 2.0),
-                        Point2::new(x + 10.0, y + 18.0),
-                    ],
-                    16.0,
+    // Draw content in the box
+    for py in 0..PREVIEW_ROWS {
+        for px in 0..PREVIEW_COLUMNS {
+            if board.get(px, py) == TileType::Tet {
+                draw_square(
+                    mb,
+                    layout.preview_square(area, px, py),
+                    layout.cell,
                     Color::new(0.0, 1.0, 1.0, 1.0),
                 )?;
             }
@@ -706,118 +108,83 @@ fn draw_next_board_contents(
     Ok(())
 }
 
-fn draw_board_grid(
-    mb: &mut graphics::MeshBuilder,
-    board: &mut [[TileType; BOARD_HEIGHT]; BOARD_WIDTH],
-) -> GameResult {
-    for y in (20..=520).step_by(20) {
-        let y = y as f32;
+fn draw_board_grid(mb: &mut graphics::MeshBuilder, layout: &Layout) -> GameResult {
+    let area = layout.playfield;
+    for row in 0..=layout.grid_rows {
+        let y = area.y + row as f32 * layout.cell;
         mb.line(
-            &[Point2::new(220.0, y), Point2::new(460.0, y)],
-            2.0,
+            &[Point2::new(area.x, y), Point2::new(area.x + area.w, y)],
+            layout.line_width(),
             Color::new(0.9, 0.9, 0.9, 4.0),
         )?;
     }
     // Draw the vertical lines for the playfield
-    for x in (220..=460).step_by(20) {
-        let x = x as f32;
+    for column in 0..=layout.grid_columns {
+        let x = area.x + column as f32 * layout.cell;
         mb.line(
-            &[Point2::new(x, 20.0), Point2::new(x, 520.0)],
-            2.0,
+            &[Point2::new(x, area.y), Point2::new(x, area.y + area.h)],
+            layout.line_width(),
             Color::new(0.9, 0.9, 0.9, 4.0),
         )?;
     }
-
-    // Draw the border squares
-    // XXX this belongs somewhere else, no need to fill the
-    // grid every time
-    for y in 0..BOARD_HEIGHT - 1 {
-        board[1][y] = TileType::Border;
-        board[12][y] = TileType::Border;
-    }
-    for x in 1..BOARD_WIDTH - 2 {
-        board[x][BOARD_HEIGHT - 2] = TileType::Border;
-    }
     Ok(())
 }
 
+// The colour for painting a piece's squares, the guideline colours for
+// the tetrominoes and round again for bigger sets.
+fn piece_color(tet_type: PieceType) -> Color {
+    const COLORS: [(f32, f32, f32); 7] = [
+        (0.0, 0.9, 0.9),
+        (0.9, 0.9, 0.0),
+        (0.6, 0.0, 0.9),
+        (0.0, 0.3, 1.0),
+        (1.0, 0.5, 0.0),
+        (0.0, 0.9, 0.0),
+        (0.9, 0.0, 0.0),
+    ];
+    let (r, g, b) = COLORS[tet_type.0 as usize % COLORS.len()];
+    Color::new(r, g, b, 1.0)
+}
+
 fn draw_board_contents(
     mb: &mut graphics::MeshBuilder,
-    board: &mut [[TileType; BOARD_HEIGHT]; BOARD_WIDTH],
+    layout: &Layout,
+    board: &Board,
 ) -> GameResult {
     // Draw content on the board
-    // We are using two values here per loop.  The px and py are the index
-    // into the board array.  The x and y are the actual locations on the
-    // screen where things will be drawn.
-    // board array.
+    // The px and py are the index into the board array, the layout
+    // tells us where on the screen that square is drawn.
     //
     // This is wider than the board grid we drew above because there is a portion
     // of the board grid that we don't display or ever expect to find a valid
     // tet placement.  This additional width is due to rotation of the I tet.  We
     // need to allow for the 4x4 square that could have an I tet be a valid
-    // positive integer.
-    for (py, y) in (20..540).step_by(20).enumerate() {
-        let mut row_count = 0;
-        for (px, x) in (200..480).step_by(20).enumerate() {
-            let x = x as f32;
-            let y = y as f32;
+    // positive integer.  Rows hidden above the playfield aren't drawn.
+    for py in layout.first_row..layout.first_row + layout.grid_rows {
+        for px in 0..board.width() {
+            let square = layout.board_square(px, py);
 
             // XXX We should draw the border once at the start
             // of the program and not have to re-draw it each time.
             // Assuming I can figure out how to leave drawings behind
             // instead of wiping the screen each time.
-            match board[px][py] {
+            match board.get(px, py) {
                 TileType::Border => {
-                    mb.line(
-                        &[
-                            Point2::new(x + 10.0, y + 2.0),
-                            Point2::new(x + 10.0, y + 18.0),
-                        ],
-                        16.0,
-                        Color::new(1.0, 0.0, 0.0, 1.0),
-                    )?;
+                    draw_square(mb, square, layout.cell, Color::new(1.0, 0.0, 0.0, 1.0))?;
                 }
                 TileType::Tet => {
-                    mb.line(
-                        &[
-                            Point2::new(x + 10.0, y + 2.0),
-                            Point2::new(x + 10.0, y + 18.0),
-                        ],
-                        16.0,
-                        Color::new(0.0, 1.0, 1.0, 1.0),
-                    )?;
+                    draw_square(mb, square, layout.cell, Color::new(0.0, 1.0, 1.0, 1.0))?;
                 }
                 TileType::Base => {
-                    row_count += 1;
-                    mb.line(
-                        &[
-                            Point2::new(x + 10.0, y + 2.0),
-                            Point2::new(x + 10.0, y + 18.0),
-                        ],
-                        16.0,
-                        Color::new(0.0, 1.0, 0.5, 1.0),
-                    )?;
+                    draw_square(mb, square, layout.cell, Color::new(0.0, 1.0, 0.5, 1.0))?;
                 }
-                _ => (),
-            }
-        }
-        if row_count >= 10 {
-            // Redraw this as a blank, but only the actual
-            // squares that pieces can operate on.
-            for (px, x) in (200..460).step_by(20).enumerate() {
-                let x = x as f32;
-                let y = y as f32;
-                // The following should be asserted
-                if board[px][py] == TileType::Base {
-                    mb.line(
-                        &[
-                            Point2::new(x + 10.0, y + 2.0),
-                            Point2::new(x + 10.0, y + 18.0),
-                        ],
-                        16.0,
-                        Color::new(0.1, 0.2, 0.3, 1.0),
-                    )?;
+                TileType::Garbage => {
+                    draw_square(mb, square, layout.cell, Color::new(0.5, 0.5, 0.5, 1.0))?;
+                }
+                TileType::Piece(tet_type) => {
+                    draw_square(mb, square, layout.cell, piece_color(tet_type))?;
                 }
+                _ => (),
             }
         }
     }
@@ -825,108 +192,43 @@ fn draw_board_contents(
 }
 
 impl MainState {
-    fn new(_ctx: &mut Context) -> GameResult<MainState> {
-        let mut q = TetQueue::new();
-
-        let piece = Piece {
-            tet_type: q.next(),
-            rotation: 0,
-            x: 6,
-            y: 0,
-        };
-        let next_piece = set_next_piece(q.peek());
-
+    fn new(ctx: &mut Context) -> GameResult<MainState> {
         let s = MainState {
-            board: [[TileType::Blank; BOARD_HEIGHT]; BOARD_WIDTH],
-            next_board: [[TileType::Blank; BOARD_HEIGHT]; BOARD_WIDTH],
-            piece,
-            next_piece,
-            piece_queue: q,
-            board_state: BoardState::Moving,
-            score: 0,
-            level: 0,
-            lines: 0,
+            scenes: SceneStack::new(Box::new(TitleScene::new())),
+            shared: Shared::new(ctx)?,
         };
         Ok(s)
     }
-}
 
-impl EventHandler for MainState {
-    fn update(&mut self, ctx: &mut Context) -> GameResult {
-        if input::keyboard::is_key_pressed(ctx, KeyCode::Q) {
-            println!("quit");
+    // Mouse positions come in window coordinates, which on a HiDPI
+    // display are smaller than the pixels we draw in.
+    fn mouse_position(ctx: &Context, x: f32, y: f32) -> (f32, f32) {
+        let (width, height) = graphics::size(ctx);
+        let (drawable_width, drawable_height) = graphics::drawable_size(ctx);
+        if width <= 0.0 || height <= 0.0 {
+            return (x, y);
         }
-        while timer::check_update_time(ctx, self.level + 1) {
-            match self.board_state {
-                BoardState::Clearing => {
-                    // Once we have done one cycle clear, we then resume
-                    // part movement and drop down pieces above our cleared
-                    // row(s)
-
-                    // Start from the bottom and work our way up.
-                    // The destination Y always starts at the highest valid
-                    // valid Y we can have pieces at.
-                    // We walk the array from the bottom up.
-                    let mut y_dest = BOARD_HEIGHT - 3;
-                    let mut cleared = 0;
-                    for y in (0..BOARD_HEIGHT - 2).rev() {
-                        let mut row_count = 0;
-                        for x in 2..BOARD_WIDTH - 2 {
-                            if self.board[x][y] == TileType::Base {
-                                row_count += 1;
-                            }
-                        }
-
-                        if row_count == 10 {
-                            cleared += 1;
-                            continue;
-                        }
-
-                        // Move the source y to the current y.
-                        for x in 2..BOARD_WIDTH - 2 {
-                            self.board[x][y_dest] = self.board[x][y];
-                        }
+        (x * drawable_width / width, y * drawable_height / height)
+    }
 
-                        y_dest -= 1;
-                    }
-                    // Clear out any rows left at the top
-                    for y in 0..=y_dest {
-                        for x in 2..BOARD_WIDTH - 2 {
-                            self.board[x][y] = TileType::Blank;
-                        }
-                    }
+    // Give the input to the scenes, and quit if they are all done.
+    fn input(&mut self, ctx: &mut Context, input: Input) {
+        if !self.scenes.input(ctx, &mut self.shared, input) {
+            quit(ctx);
+        }
+    }
+}
 
-                    self.lines += cleared;
-                    if self.lines >= 10 {
-                        self.lines = 0;
-                        self.level += 1;
-                    }
-                    self.score += get_score(cleared, self.level);
+// Draw a line of HUD text at the given position, sized to fit the layout.
+fn draw_text(ctx: &mut Context, layout: &Layout, text: String, (x, y): (f32, f32)) -> GameResult {
+    let text = Text::new(TextFragment::new(text).scale(Scale::uniform(layout.font_size())));
+    graphics::draw(ctx, &text, (Point2::new(x, y), graphics::WHITE))
+}
 
-                    // Now make the new piece.
-                    self.board_state = place_new_piece(
-                        &mut self.board,
-                        &mut self.piece,
-                        &mut self.piece_queue,
-                        &mut self.next_board,
-                        &mut self.next_piece,
-                    );
-                }
-                BoardState::Paused => (),
-                BoardState::Over => (),
-                BoardState::Moving => {
-                    if !move_tet_down(&mut self.board, &mut self.piece) {
-                        self.board_state = convert_and_check(
-                            &mut self.board,
-                            &mut self.piece,
-                            &mut self.piece_queue,
-                            &mut self.next_board,
-                            &mut self.next_piece,
-                        );
-                        // Should we always set new piece here?? ZZZ
-                    }
-                }
-            }
+impl EventHandler for MainState {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        if !self.scenes.update(ctx, &mut self.shared)? {
+            quit(ctx);
         }
         Ok(())
     }
@@ -938,185 +240,95 @@ impl EventHandler for MainState {
         _keymod: KeyMods,
         repeat: bool,
     ) {
-        if !repeat {
+        if repeat {
+            return;
+        }
+        // Keys that work everywhere, except when typing
+        if !self.scenes.takes_text() {
             match keycode {
-                input::keyboard::KeyCode::Q => quit(ctx),
-                // Pause the game
-                input::keyboard::KeyCode::P => {
-                    if self.board_state == BoardState::Paused {
-                        self.board_state = BoardState::Moving;
-                    } else {
-                        // XXX Don't pause if game over...
-                        self.board_state = BoardState::Paused;
-                    }
-                }
-                // Rotate
-                input::keyboard::KeyCode::W => {
-                    // Check for rotation being legit
-                    // Erase current location
-
-                    let original_rotation = self.piece.rotation;
-                    self.piece.rotation += 1;
-                    if self.piece.rotation > 3 {
-                        self.piece.rotation = 0;
-                    }
-                    if validate_move(self.board, self.piece) {
-                        let new_rotation = self.piece.rotation;
-                        self.piece.rotation = original_rotation;
-                        plot_tet(&mut self.board, self.piece, TileType::Blank);
-                        self.piece.rotation = new_rotation;
-                    } else {
-                        self.piece.rotation = original_rotation;
-                    }
-                }
-                // Move left
-                input::keyboard::KeyCode::A => {
-                    // The I piece has a rotation that could have an x value
-                    // of zero, so we have to prevent it underflowing
-                    if self.piece.x > 0 {
-                        self.piece.x -= 1;
-                        if validate_move(self.board, self.piece) {
-                            self.piece.x += 1;
-                            plot_tet(&mut self.board, self.piece, TileType::Blank);
-                            self.piece.x -= 1;
-                        } else {
-                            self.piece.x += 1;
-                        }
-                    }
-                }
-                // Move right
-                input::keyboard::KeyCode::D => {
-                    self.piece.x += 1;
-                    if validate_move(self.board, self.piece) {
-                        self.piece.x -= 1;
-                        plot_tet(&mut self.board, self.piece, TileType::Blank);
-                        self.piece.x += 1;
-                    } else {
-                        self.piece.x -= 1;
-                    }
-                }
-                // Single down
-                input::keyboard::KeyCode::S => {
-                    if !move_tet_down(&mut self.board, &mut self.piece) {
-                        self.board_state = convert_and_check(
-                            &mut self.board,
-                            &mut self.piece,
-                            &mut self.piece_queue,
-                            &mut self.next_board,
-                            &mut self.next_piece,
-                        );
-                    }
-                }
-                // All the way down
-                input::keyboard::KeyCode::Space => {
-                    while move_tet_down(&mut self.board, &mut self.piece) {
-                        println!("Down");
-                        // XXX This needs to not do the convert and check yet,
-                        // let the timer run out (so we can slide to the side)
-                        // before making final the piece.
-                    }
-                    self.board_state = convert_and_check(
-                        &mut self.board,
-                        &mut self.piece,
-                        &mut self.piece_queue,
-                        &mut self.next_board,
-                        &mut self.next_piece,
-                    );
-                }
-                // Begin debug commands
-                input::keyboard::KeyCode::Z => {
-                    println!("{:#?}", self.board);
-                }
-                input::keyboard::KeyCode::C => {
-                    self.board_state = convert_and_check(
-                        &mut self.board,
-                        &mut self.piece,
-                        &mut self.piece_queue,
-                        &mut self.next_board,
-                        &mut self.next_piece,
-                    );
-                }
-                input::keyboard::KeyCode::Y => {
-                    if self.level > 1 {
-                        self.level -= 1;
-                    }
-                }
-                input::keyboard::KeyCode::U => {
-                    if self.level < 255 {
-                        self.level += 1;
-                    }
-                }
-                input::keyboard::KeyCode::X => {
-                    plot_tet(&mut self.board, self.piece, TileType::Blank);
-                    self.piece.y -= 1;
-                }
-                input::keyboard::KeyCode::I => {
-                    plot_tet(&mut self.board, self.piece, TileType::Blank);
-                    self.piece.tet_type = match self.piece.tet_type {
-                        Tetrominoes::I => Tetrominoes::O,
-                        Tetrominoes::O => Tetrominoes::T,
-                        Tetrominoes::T => Tetrominoes::J,
-                        Tetrominoes::J => Tetrominoes::L,
-                        Tetrominoes::L => Tetrominoes::S,
-                        Tetrominoes::S => Tetrominoes::Z,
-                        _ => Tetrominoes::I,
-                    };
-                }
+                KeyCode::M => return self.shared.audio.toggle_mute(),
+                KeyCode::Minus => return self.shared.audio.quieter(),
+                KeyCode::Equals => return self.shared.audio.louder(),
                 _ => (),
             }
         }
+        if keycode == KeyCode::F11 {
+            if let Err(e) = self.shared.toggle_fullscreen(ctx) {
+                println!("Unable to change fullscreen mode: {}", e);
+            }
+            return;
+        }
+        self.input(ctx, Input::Key(keycode));
     }
 
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        graphics::clear(ctx, [0.1, 0.2, 0.3, 1.0].into());
+    fn text_input_event(&mut self, ctx: &mut Context, character: char) {
+        if self.scenes.takes_text() {
+            self.input(ctx, Input::Text(character));
+        }
+    }
 
-        // Text
-        let text = graphics::Text::new(format!("Board state:{:#?}", self.board_state));
-        graphics::draw(ctx, &text, (Point2::new(10.0, 60.0), graphics::WHITE))?;
-        let text = graphics::Text::new(format!("Score:{}", self.score));
-        graphics::draw(ctx, &text, (Point2::new(10.0, 80.0), graphics::WHITE))?;
-        let text = graphics::Text::new(format!("Lines:{}", self.lines));
-        graphics::draw(ctx, &text, (Point2::new(10.0, 100.0), graphics::WHITE))?;
-        let text = graphics::Text::new(format!("Level:{}", self.level));
-        graphics::draw(ctx, &text, (Point2::new(10.0, 120.0), graphics::WHITE))?;
-        let next = self.piece_queue.peek();
-        let text = graphics::Text::new(format!("Next: {:?}", next));
-        graphics::draw(ctx, &text, (Point2::new(10.0, 140.0), graphics::WHITE))?;
+    fn gamepad_button_down_event(&mut self, ctx: &mut Context, btn: Button, _id: GamepadId) {
+        self.input(ctx, Input::Button(btn));
+    }
 
-        // The board grid
-        // Horizional lines for the playfield
-        let mb = &mut graphics::MeshBuilder::new();
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        let (x, y) = MainState::mouse_position(ctx, x, y);
+        self.input(ctx, Input::MouseDown(button, x, y));
+    }
 
-        draw_board_grid(mb, &mut self.board)?;
-        //
-        // Any board state except for clearing, we plot the tet at its
-        // current coordinates.  When clearing, the piece location has become
-        // base, so don't draw anything.
-        if self.board_state != BoardState::Clearing {
-            plot_tet(&mut self.board, self.piece, TileType::Tet);
-        }
+    fn mouse_button_up_event(&mut self, ctx: &mut Context, button: MouseButton, _x: f32, _y: f32) {
+        self.input(ctx, Input::MouseUp(button));
+    }
 
-        draw_board_contents(mb, &mut self.board)?;
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        let (x, y) = MainState::mouse_position(ctx, x, y);
+        self.input(ctx, Input::MouseMove(x, y));
+    }
 
-        draw_next_board_grid(mb)?;
-        // When we add a game start button, we can do this less
-        // frequently.  The only time it needs to change is on start
-        // and when a piece has reached the bottom.  XXX
-        plot_tet(&mut self.next_board, self.next_piece, TileType::Tet);
-        draw_next_board_contents(mb, &mut self.next_board)?;
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, BACKGROUND.into());
 
-        let m = mb.build(ctx)?;
-        graphics::draw(ctx, &m, DrawParam::new())?;
+        self.scenes.draw(ctx, &mut self.shared)?;
 
         // Finished drawing, show it all on the screen!
         graphics::present(ctx)?;
 
         Ok(())
     }
+
+    fn quit_event(&mut self, ctx: &mut Context) -> bool {
+        self.scenes.quit(ctx, &mut self.shared);
+        false
+    }
+
+    fn resize_event(&mut self, ctx: &mut Context, _width: f32, _height: f32) {
+        if let Err(e) = self.shared.update_layout(ctx) {
+            println!("Unable to resize: {}", e);
+        }
+    }
 }
 
 pub fn main() -> GameResult {
-    let cb = ggez::ContextBuilder::new("drawing", "ggez");
+    // `ttet fumen ...` works with fumen on the command line, without
+    // opening the game.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("fumen") {
+        fumen::command(&args[1..]);
+        return Ok(());
+    }
+
+    // Sounds and music are loaded from the resources directory
+    let mut resources = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    resources.push("resources");
+
+    let cb = ggez::ContextBuilder::new("drawing", "ggez")
+        .window_mode(
+            WindowMode::default()
+                .dimensions(800.0, 600.0)
+                .min_dimensions(320.0, 240.0)
+                .resizable(true),
+        )
+        .add_resource_path(resources);
 
     let (ctx, events_loop) = &mut cb.build()?;
     graphics::set_window_title(ctx, "Work In Progress");
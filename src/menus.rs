@@ -0,0 +1,749 @@
+use std::time::Duration;
+
+use ggez::event::{Button, KeyCode};
+use ggez::graphics;
+use ggez::graphics::{Color, DrawMode, Rect};
+use ggez::{filesystem, timer, Context, GameResult};
+
+use crate::board::{MAX_HEIGHT, MAX_HIDDEN, MAX_WIDTH, MIN_HEIGHT, MIN_WIDTH};
+use crate::editor::EditorScene;
+use crate::gravity::CURVES;
+use crate::highscores::{format_time, Entry, Table, Unit, MARATHON, TABLES};
+use crate::mode::{Mode, BOT_SPEEDS, SPRINT_GOALS};
+use crate::pieces::Pieces;
+use crate::play::{split_lines, PlayScene};
+use crate::save::SavedGame;
+use crate::scene::{draw_centered, Input, Menu, MenuCommand, Scene, Shared, Transition};
+use crate::scoring::Scoring;
+
+// The longest name we take for the high score table.
+const NAME_LENGTH: usize = 12;
+
+// How much the volume settings change with each press.
+const VOLUME_STEP: f32 = 0.1;
+
+// How long the title screen sits untouched before the demo starts.
+const ATTRACT_TIME: Duration = Duration::from_secs(20);
+
+// The first thing you see.
+pub struct TitleScene {
+    menu: Menu,
+    // There is a saved game to continue.
+    can_continue: bool,
+    // When the title screen was last used, or last uncovered.
+    idle_since: Duration,
+    // Look for a saved game on the next update.  Set when the title
+    // screen first shows and whenever it is uncovered, so the disk
+    // isn't checked every frame.
+    stale: bool,
+}
+
+impl TitleScene {
+    const ITEMS: [&'static str; 6] = ["Play", "Editor", "Demo", "High scores", "Settings", "Quit"];
+
+    pub fn new() -> TitleScene {
+        TitleScene {
+            menu: Menu::new(TitleScene::ITEMS.len()),
+            can_continue: false,
+            idle_since: Duration::from_secs(0),
+            stale: true,
+        }
+    }
+
+    // Continue comes first when there is a saved game.
+    fn items(&self) -> Vec<&'static str> {
+        let mut items = Vec::new();
+        if self.can_continue {
+            items.push("Continue");
+        }
+        items.extend_from_slice(&TitleScene::ITEMS);
+        items
+    }
+}
+
+impl Scene for TitleScene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        if self.stale {
+            self.stale = false;
+            let can_continue = SavedGame::exists(filesystem::user_data_dir(ctx));
+            if can_continue != self.can_continue {
+                self.can_continue = can_continue;
+                self.menu = Menu::new(self.items().len());
+            }
+        }
+        // Left alone long enough, the bot shows how it's done
+        let now = timer::time_since_start(ctx);
+        if now - self.idle_since >= ATTRACT_TIME {
+            self.idle_since = now;
+            return Ok(Transition::Push(Box::new(PlayScene::demo(shared))));
+        }
+        Ok(Transition::None)
+    }
+
+    fn covered_update(&mut self, ctx: &mut Context) {
+        self.idle_since = timer::time_since_start(ctx);
+        self.stale = true;
+    }
+
+    fn input(&mut self, ctx: &mut Context, shared: &mut Shared, input: Input) -> Transition {
+        self.idle_since = timer::time_since_start(ctx);
+        if input == Input::Key(KeyCode::Q) {
+            return Transition::Quit;
+        }
+        let command = match input.menu_command() {
+            Some(command) => command,
+            None => return Transition::None,
+        };
+        let items = self.items();
+        match self.menu.command(command).map(|i| items[i]) {
+            Some("Continue") => match SavedGame::take(filesystem::user_data_dir(ctx)) {
+                Some(saved) => Transition::Push(Box::new(PlayScene::resume(shared, saved))),
+                None => Transition::None,
+            },
+            Some("Play") => Transition::Push(Box::new(ModeSelectScene::new())),
+            Some("Editor") => Transition::Push(Box::new(EditorScene::new(shared))),
+            Some("Demo") => Transition::Push(Box::new(PlayScene::demo(shared))),
+            Some("High scores") => Transition::Push(Box::new(HighScoresScene::new(MARATHON))),
+            Some("Settings") => Transition::Push(Box::new(SettingsScene::new(shared))),
+            Some(_) => Transition::Quit,
+            None => Transition::None,
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult {
+        let items: Vec<String> = self.items().iter().map(|s| s.to_string()).collect();
+        self.menu.draw(ctx, &shared.layout, "ttet", &items)
+    }
+}
+
+// Pick which kind of game to play.  Left and right change the line
+// goal for a sprint, whether garbage rises in a dig and how fast the
+// bot is in versus.
+pub struct ModeSelectScene {
+    menu: Menu,
+    // Which of SPRINT_GOALS is picked, whether the garbage rises in a
+    // dig and which of BOT_SPEEDS is picked.
+    goal: usize,
+    rising: bool,
+    speed: usize,
+}
+
+impl ModeSelectScene {
+    pub fn new() -> ModeSelectScene {
+        let mut scene = ModeSelectScene {
+            menu: Menu::new(0),
+            goal: 1,
+            rising: false,
+            speed: 1,
+        };
+        scene.menu = Menu::new(scene.items().len());
+        scene
+    }
+
+    fn items(&self) -> Vec<String> {
+        vec![
+            "Marathon".to_string(),
+            format!("< Sprint: {} lines >", SPRINT_GOALS[self.goal]),
+            "Ultra: 2 minutes".to_string(),
+            format!("< Dig: {} >", if self.rising { "rising" } else { "steady" }),
+            "Zen".to_string(),
+            format!("< Versus: {} bot >", BOT_SPEEDS[self.speed].0),
+            "Back".to_string(),
+        ]
+    }
+}
+
+impl Scene for ModeSelectScene {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    fn input(&mut self, _ctx: &mut Context, shared: &mut Shared, input: Input) -> Transition {
+        let goals = SPRINT_GOALS.len();
+        let speeds = BOT_SPEEDS.len();
+        let mode = match input.menu_command() {
+            Some(MenuCommand::Back) => return Transition::Pop,
+            Some(MenuCommand::Left) if self.menu.selected == 1 => {
+                self.goal = (self.goal + goals - 1) % goals;
+                return Transition::None;
+            }
+            Some(MenuCommand::Right) if self.menu.selected == 1 => {
+                self.goal = (self.goal + 1) % goals;
+                return Transition::None;
+            }
+            Some(MenuCommand::Left) | Some(MenuCommand::Right) if self.menu.selected == 3 => {
+                self.rising = !self.rising;
+                return Transition::None;
+            }
+            Some(MenuCommand::Left) if self.menu.selected == 5 => {
+                self.speed = (self.speed + speeds - 1) % speeds;
+                return Transition::None;
+            }
+            Some(MenuCommand::Right) if self.menu.selected == 5 => {
+                self.speed = (self.speed + 1) % speeds;
+                return Transition::None;
+            }
+            Some(command) => match self.menu.command(command) {
+                Some(0) => Mode::Marathon,
+                Some(1) => Mode::Sprint(SPRINT_GOALS[self.goal]),
+                Some(2) => Mode::Ultra,
+                Some(3) => Mode::Dig {
+                    rising: self.rising,
+                },
+                Some(4) => Mode::Zen,
+                Some(5) => Mode::Versus(BOT_SPEEDS[self.speed].1),
+                Some(_) => return Transition::Pop,
+                None => return Transition::None,
+            },
+            None => return Transition::None,
+        };
+        Transition::Replace(Box::new(PlayScene::new(shared, mode)))
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult {
+        let items = self.items();
+        self.menu.draw(ctx, &shared.layout, "Mode", &items)
+    }
+}
+
+// Sound, animations and the window.
+pub struct SettingsScene {
+    menu: Menu,
+}
+
+impl SettingsScene {
+    pub fn new(shared: &Shared) -> SettingsScene {
+        SettingsScene {
+            menu: Menu::new(SettingsScene::items(shared).len()),
+        }
+    }
+
+    fn items(shared: &Shared) -> Vec<String> {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let animations = shared.animations;
+        vec![
+            format!("Line clear animation: {}", on_off(animations.line_clear)),
+            format!("Lock flash: {}", on_off(animations.lock_flash)),
+            format!("Hard drop trail: {}", on_off(animations.hard_drop_trail)),
+            format!("Screen shake: {}", on_off(animations.screen_shake)),
+            format!(
+                "Effects volume: {:.0}%",
+                shared.audio.effects_volume * 100.0
+            ),
+            format!("Music volume: {:.0}%", shared.audio.music_volume * 100.0),
+            format!("Sound: {}", on_off(!shared.audio.muted())),
+            format!("Fullscreen: {}", on_off(shared.fullscreen)),
+            format!("Gravity: {}", shared.options.gravity.name()),
+            format!("All-spin: {}", on_off(shared.options.all_spin)),
+            format!(
+                "Scoring: {}",
+                shared
+                    .options
+                    .scoring
+                    .map_or("by mode".to_string(), |s| s.name())
+            ),
+            format!(
+                "Pieces: {}",
+                match (shared.options.pieces, &shared.piece_set) {
+                    (Pieces::Custom, Some(set)) => set.name.clone(),
+                    (pieces, _) => pieces.name(),
+                }
+            ),
+            format!("Board width: {}", shared.options.size.width),
+            format!("Board height: {}", shared.options.size.height),
+            format!("Hidden rows: {}", shared.options.size.hidden),
+            "Back".to_string(),
+        ]
+    }
+}
+
+// Turn a volume up or down a step, keeping it between 0 and 1.
+fn step_volume(volume: &mut f32, command: MenuCommand) {
+    match command {
+        MenuCommand::Left => *volume = (*volume - VOLUME_STEP).max(0.0),
+        MenuCommand::Right | MenuCommand::Select => *volume = (*volume + VOLUME_STEP).min(1.0),
+        _ => (),
+    }
+}
+
+// The board can't be narrower than the widest piece in the set.
+fn min_width(shared: &Shared) -> usize {
+    let pieces = shared.options.pieces.set(shared.piece_set.as_ref());
+    MIN_WIDTH.max(pieces.widest())
+}
+
+// Make the board a row or column bigger or smaller, keeping it in the
+// range given.
+fn step_size(value: &mut usize, min: usize, max: usize, command: MenuCommand) {
+    match command {
+        MenuCommand::Left => *value = (*value - 1).max(min),
+        MenuCommand::Right | MenuCommand::Select => *value = (*value + 1).min(max),
+        _ => (),
+    }
+}
+
+impl Scene for SettingsScene {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    fn input(&mut self, ctx: &mut Context, shared: &mut Shared, input: Input) -> Transition {
+        let command = match input.menu_command() {
+            Some(MenuCommand::Back) => return Transition::Pop,
+            Some(command) => command,
+            None => return Transition::None,
+        };
+        self.menu.command(command);
+        let change = matches!(
+            command,
+            MenuCommand::Left | MenuCommand::Right | MenuCommand::Select
+        );
+        if !change {
+            return Transition::None;
+        }
+
+        let animations = &mut shared.animations;
+        match self.menu.selected {
+            0 => animations.line_clear = !animations.line_clear,
+            1 => animations.lock_flash = !animations.lock_flash,
+            2 => animations.hard_drop_trail = !animations.hard_drop_trail,
+            3 => animations.screen_shake = !animations.screen_shake,
+            4 => {
+                step_volume(&mut shared.audio.effects_volume, command);
+                shared.audio.apply_volume();
+            }
+            5 => {
+                step_volume(&mut shared.audio.music_volume, command);
+                shared.audio.apply_volume();
+            }
+            6 => shared.audio.toggle_mute(),
+            7 => {
+                if let Err(e) = shared.toggle_fullscreen(ctx) {
+                    println!("Unable to change fullscreen mode: {}", e);
+                }
+            }
+            8 => {
+                // Step through the curves, the change applies from the
+                // next game
+                let curves = CURVES.len();
+                let at = CURVES
+                    .iter()
+                    .position(|c| *c == shared.options.gravity)
+                    .unwrap_or(0);
+                let next = match command {
+                    MenuCommand::Left => (at + curves - 1) % curves,
+                    _ => (at + 1) % curves,
+                };
+                shared.options.gravity = CURVES[next];
+            }
+            9 => shared.options.all_spin = !shared.options.all_spin,
+            10 => {
+                // By mode, then each system in turn.  Custom is only
+                // there with a table to use.
+                let mut choices = vec![None, Some(Scoring::Nes), Some(Scoring::Guideline)];
+                if shared.score_table.is_some() {
+                    choices.push(Some(Scoring::Custom));
+                }
+                let at = choices
+                    .iter()
+                    .position(|c| *c == shared.options.scoring)
+                    .unwrap_or(0);
+                let next = match command {
+                    MenuCommand::Left => (at + choices.len() - 1) % choices.len(),
+                    _ => (at + 1) % choices.len(),
+                };
+                shared.options.scoring = choices[next];
+            }
+            11 => {
+                // Custom is only there with a piece set to use
+                let mut choices = vec![Pieces::Tetrominoes, Pieces::Pentominoes, Pieces::Beginner];
+                if shared.piece_set.is_some() {
+                    choices.push(Pieces::Custom);
+                }
+                let at = choices
+                    .iter()
+                    .position(|c| *c == shared.options.pieces)
+                    .unwrap_or(0);
+                let next = match command {
+                    MenuCommand::Left => (at + choices.len() - 1) % choices.len(),
+                    _ => (at + 1) % choices.len(),
+                };
+                shared.options.pieces = choices[next];
+                // Wider pieces need a wider board
+                let width = min_width(shared);
+                shared.options.size.width = shared.options.size.width.max(width);
+            }
+            // The size applies from the next game
+            12 => {
+                let width = min_width(shared);
+                step_size(&mut shared.options.size.width, width, MAX_WIDTH, command)
+            }
+            13 => step_size(
+                &mut shared.options.size.height,
+                MIN_HEIGHT,
+                MAX_HEIGHT,
+                command,
+            ),
+            14 => step_size(&mut shared.options.size.hidden, 0, MAX_HIDDEN, command),
+            _ => {
+                if command == MenuCommand::Select {
+                    return Transition::Pop;
+                }
+            }
+        }
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult {
+        let items = SettingsScene::items(shared);
+        self.menu.draw(ctx, &shared.layout, "Settings", &items)
+    }
+}
+
+// Darken whatever is underneath, for menus that sit over the game.
+fn draw_shade(ctx: &mut Context) -> GameResult {
+    let (width, height) = graphics::drawable_size(ctx);
+    let shade = graphics::Mesh::new_rectangle(
+        ctx,
+        DrawMode::fill(),
+        Rect::new(0.0, 0.0, width, height),
+        Color::new(0.0, 0.0, 0.0, 0.7),
+    )?;
+    graphics::draw(ctx, &shade, (ggez::nalgebra::Point2::new(0.0, 0.0),))
+}
+
+// The menu over a paused game.
+pub struct PauseScene {
+    menu: Menu,
+}
+
+impl PauseScene {
+    const ITEMS: [&'static str; 4] = ["Resume", "Settings", "Save and quit", "Quit to title"];
+
+    pub fn new() -> PauseScene {
+        PauseScene {
+            menu: Menu::new(PauseScene::ITEMS.len()),
+        }
+    }
+}
+
+impl Scene for PauseScene {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    fn input(&mut self, _ctx: &mut Context, shared: &mut Shared, input: Input) -> Transition {
+        if input.is_pause() {
+            return Transition::Pop;
+        }
+        match input.menu_command() {
+            Some(MenuCommand::Back) => Transition::Pop,
+            Some(command) => match self.menu.command(command) {
+                Some(0) => Transition::Pop,
+                Some(1) => Transition::Push(Box::new(SettingsScene::new(shared))),
+                // The game underneath saves itself on the way out
+                Some(2) => {
+                    shared.save_and_quit = true;
+                    Transition::Pop
+                }
+                Some(_) => {
+                    shared.audio.stop_music();
+                    Transition::Home
+                }
+                None => Transition::None,
+            },
+            None => Transition::None,
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult {
+        draw_shade(ctx)?;
+        let items: Vec<String> = PauseScene::ITEMS.iter().map(|s| s.to_string()).collect();
+        self.menu.draw(ctx, &shared.layout, "Paused", &items)
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+// How a finished game went.
+#[derive(Debug, Clone)]
+pub struct Results {
+    pub mode: Mode,
+    // Whether the goal was reached or the time ran out, rather than
+    // the pieces reaching the top.
+    pub finished: bool,
+    // The value for the high score table, the score or the time.
+    pub value: u64,
+    pub seed: u64,
+    // Played with the mode's own rules, scoring and pieces, so it
+    // can go on the high score table.
+    pub ranked: bool,
+    // Sprint split times in milliseconds, and those of the best time
+    // before this game.
+    pub splits: Vec<u64>,
+    pub best_splits: Vec<u64>,
+    pub score: u32,
+    pub level: u32,
+    pub lines: u32,
+    pub pieces: u32,
+    pub tetrises: u32,
+    pub t_spins: u32,
+    pub max_combo: u32,
+    pub perfect_clears: u32,
+    // Pieces taken back, in the modes for practice.
+    pub undos: u32,
+}
+
+// The results of a finished game, with the choice to go again.
+pub struct GameOverScene {
+    results: Results,
+    high_score: bool,
+    menu: Menu,
+}
+
+impl GameOverScene {
+    const ITEMS: [&'static str; 3] = ["Retry", "High scores", "Title"];
+
+    pub fn new(results: Results, high_score: bool) -> GameOverScene {
+        GameOverScene {
+            results,
+            high_score,
+            menu: Menu::new(GameOverScene::ITEMS.len()),
+        }
+    }
+}
+
+impl Scene for GameOverScene {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    fn input(&mut self, _ctx: &mut Context, shared: &mut Shared, input: Input) -> Transition {
+        match input.menu_command() {
+            Some(MenuCommand::Back) => Transition::Home,
+            Some(command) => match self.menu.command(command) {
+                Some(0) => Transition::Replace(Box::new(PlayScene::new(shared, self.results.mode))),
+                Some(1) => {
+                    let table = self.results.mode.table().unwrap_or(MARATHON);
+                    Transition::Push(Box::new(HighScoresScene::new(table)))
+                }
+                Some(_) => Transition::Home,
+                None => Transition::None,
+            },
+            None => Transition::None,
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult {
+        let layout = shared.layout;
+        let results = &self.results;
+        let mut lines = Vec::new();
+        let race = results.mode.table().map(|t| t.unit) == Some(Unit::Millis);
+        if race && results.finished {
+            lines.push(format!("Time: {}", format_time(results.value)));
+        }
+        lines.extend(split_lines(&results.splits, &results.best_splits));
+        lines.extend(vec![
+            format!("Score: {}", results.score),
+            format!("Level: {}", results.level),
+            format!("Lines: {}", results.lines),
+            format!("Pieces: {}", results.pieces),
+            format!("Tetrises: {}", results.tetrises),
+            format!("T-Spins: {}", results.t_spins),
+            format!("Best combo: {}", results.max_combo),
+            format!("Perfect clears: {}", results.perfect_clears),
+        ]);
+        if results.undos > 0 {
+            lines.push(format!("Undos: {} (practice, not ranked)", results.undos));
+        }
+        if !results.ranked && results.mode.table().is_some() {
+            lines.push("Not ranked, the settings changed the game".to_string());
+        }
+        if self.high_score {
+            lines.push("New high score!".to_string());
+        }
+        let mut y = layout.playfield.y + layout.playfield.h / 2.0 + layout.cell * 2.0;
+        for line in lines.iter() {
+            draw_centered(ctx, line, y, layout.font_size(), graphics::WHITE)?;
+            y += layout.line_height();
+        }
+        let items: Vec<String> = GameOverScene::ITEMS.iter().map(|s| s.to_string()).collect();
+        let title = match (results.mode, results.finished) {
+            (Mode::Versus(_), true) => "You win",
+            (Mode::Versus(_), false) => "The bot wins",
+            (_, true) => "Finished",
+            (_, false) => "Game Over",
+        };
+        self.menu.draw(ctx, &layout, title, &items)
+    }
+}
+
+// Type in a name for the high score table.  With a gamepad, up and
+// down change the last letter and right adds another.
+pub struct NameEntryScene {
+    results: Results,
+    name: String,
+}
+
+impl NameEntryScene {
+    pub fn new(results: Results) -> NameEntryScene {
+        NameEntryScene {
+            results,
+            name: String::new(),
+        }
+    }
+
+    fn finish(&mut self, shared: &mut Shared) -> Transition {
+        let name = if self.name.trim().is_empty() {
+            "Player".to_string()
+        } else {
+            self.name.trim().to_string()
+        };
+        let results = &self.results;
+        let mut entry = Entry::new(name, results.value, results.seed, results.mode.name());
+        entry.splits = results.splits.clone();
+        if let Some(table) = results.mode.table() {
+            shared.high_scores.add(table, entry);
+        }
+        Transition::Replace(Box::new(GameOverScene::new(results.clone(), true)))
+    }
+
+    // Step the last letter of the name through the alphabet.
+    fn change_letter(&mut self, up: bool) {
+        let last = self.name.pop().unwrap_or('A');
+        let next = match (last, up) {
+            ('Z', true) => 'A',
+            ('A', false) => 'Z',
+            (c, true) if c.is_ascii_uppercase() => (c as u8 + 1) as char,
+            (c, false) if c.is_ascii_uppercase() => (c as u8 - 1) as char,
+            _ => 'A',
+        };
+        self.name.push(next);
+    }
+}
+
+impl Scene for NameEntryScene {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    fn input(&mut self, _ctx: &mut Context, shared: &mut Shared, input: Input) -> Transition {
+        match input {
+            Input::Text(c) if !c.is_control() && self.name.len() < NAME_LENGTH => {
+                self.name.push(c);
+            }
+            Input::Key(KeyCode::Back) | Input::Button(Button::DPadLeft) => {
+                self.name.pop();
+            }
+            Input::Key(KeyCode::Return)
+            | Input::Button(Button::South)
+            | Input::Button(Button::Start) => return self.finish(shared),
+            Input::Button(Button::DPadUp) => self.change_letter(true),
+            Input::Button(Button::DPadDown) => self.change_letter(false),
+            Input::Button(Button::DPadRight) if self.name.len() < NAME_LENGTH => {
+                self.name.push('A');
+            }
+            _ => (),
+        }
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult {
+        let layout = shared.layout;
+        let mut y = layout.playfield.y + layout.cell * 4.0;
+        draw_centered(
+            ctx,
+            "New high score!",
+            y,
+            layout.font_size() * 2.0,
+            graphics::WHITE,
+        )?;
+        y += layout.cell * 4.0;
+        let results = &self.results;
+        let table = results.mode.table().unwrap_or(MARATHON);
+        let score = format!("{}: {}", table.title, table.format_value(results.value));
+        draw_centered(ctx, &score, y, layout.font_size(), graphics::WHITE)?;
+        y += layout.line_height() * 2.0;
+        draw_centered(
+            ctx,
+            "Enter your name:",
+            y,
+            layout.font_size(),
+            graphics::WHITE,
+        )?;
+        y += layout.line_height() * 1.5;
+        let name = format!("{}_", self.name);
+        draw_centered(
+            ctx,
+            &name,
+            y,
+            layout.font_size(),
+            Color::new(1.0, 1.0, 0.0, 1.0),
+        )
+    }
+
+    fn takes_text(&self) -> bool {
+        true
+    }
+}
+
+// The high score tables, one at a time.  Left and right switch
+// between them.
+pub struct HighScoresScene {
+    table: usize,
+}
+
+impl HighScoresScene {
+    pub fn new(table: Table) -> HighScoresScene {
+        HighScoresScene {
+            table: TABLES.iter().position(|t| *t == table).unwrap_or(0),
+        }
+    }
+}
+
+impl Scene for HighScoresScene {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> GameResult<Transition> {
+        Ok(Transition::None)
+    }
+
+    fn input(&mut self, _ctx: &mut Context, _shared: &mut Shared, input: Input) -> Transition {
+        match input.menu_command() {
+            Some(MenuCommand::Back) | Some(MenuCommand::Select) => return Transition::Pop,
+            Some(MenuCommand::Left) => self.table = (self.table + TABLES.len() - 1) % TABLES.len(),
+            Some(MenuCommand::Right) => self.table = (self.table + 1) % TABLES.len(),
+            _ => (),
+        }
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult {
+        let layout = shared.layout;
+        let table = TABLES[self.table];
+        let mut y = layout.playfield.y + layout.cell * 4.0;
+        let title = format!("< {} >", table.title);
+        draw_centered(ctx, &title, y, layout.font_size() * 2.0, graphics::WHITE)?;
+        y += layout.cell * 4.0;
+        let entries = shared.high_scores.entries(table);
+        if entries.is_empty() {
+            draw_centered(ctx, "None yet", y, layout.font_size(), graphics::WHITE)?;
+        }
+        for (i, entry) in entries.iter().enumerate() {
+            let mut line = format!(
+                "{:2}. {:<12} {:>10}  {}  seed {:016x}",
+                i + 1,
+                entry.name,
+                table.format_value(entry.value),
+                entry.format_date(),
+                entry.seed
+            );
+            if !entry.settings.is_empty() {
+                line.push_str(&format!("  {}", entry.settings));
+            }
+            draw_centered(ctx, &line, y, layout.font_size(), graphics::WHITE)?;
+            y += layout.line_height();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::Size;
+use crate::engine::{Rules, TopOut};
+use crate::gravity::{Gravity, FRAMES_PER_SECOND};
+use crate::highscores::{
+    Table, DIG, DIG_RISING, MARATHON, SPRINT_100, SPRINT_20, SPRINT_40, ULTRA,
+};
+use crate::pieces::{PieceSet, Pieces};
+use crate::scoring::Scoring;
+
+// The line goals a sprint can have.
+pub const SPRINT_GOALS: [u32; 3] = [20, 40, 100];
+
+// How long an Ultra game lasts.
+const ULTRA_TIME: Duration = Duration::from_secs(120);
+
+// The rows of garbage a dig starts with, and how often another comes
+// in when it is rising.
+const DIG_ROWS: u32 = 10;
+const DIG_RISE_TIME: Duration = Duration::from_secs(5);
+
+// How fast the bot can be in versus, by the milliseconds between its
+// moves.  It takes five or six moves to place most pieces.
+pub const BOT_SPEEDS: [(&str, u32); 3] = [("slow", 400), ("steady", 200), ("fast", 80)];
+
+// The choices from the settings menu that change how the game plays.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PlayOptions {
+    // The speed curve for the modes that get faster.
+    pub gravity: Gravity,
+    // Spins count for every piece, not just T.
+    pub all_spin: bool,
+    // The scoring system for every mode, or none to use each mode's
+    // own.
+    pub scoring: Option<Scoring>,
+    // The set of pieces to play with.
+    pub pieces: Pieces,
+    // The size of the playfield.
+    pub size: Size,
+}
+
+impl Default for PlayOptions {
+    fn default() -> PlayOptions {
+        PlayOptions {
+            gravity: Gravity::Guideline,
+            all_spin: false,
+            scoring: None,
+            pieces: Pieces::Tetrominoes,
+            size: Size::default(),
+        }
+    }
+}
+
+// The ways to play.  Each one is a set of rules for the engine, along
+// with how its games are ranked.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Mode {
+    // Play until the pieces reach the top.
+    Marathon,
+    // Clear this many lines as quickly as possible.
+    Sprint(u32),
+    // Score as much as possible in two minutes.
+    Ultra,
+    // Clear away rows of garbage as quickly as possible, with more
+    // coming in from below if it is rising.
+    Dig { rising: bool },
+    // Play for as long as you like.  Reaching the top just empties
+    // the board.
+    Zen,
+    // Play from a position built in the editor.
+    Sandbox,
+    // Send garbage back and forth with the bot, which makes a move
+    // this many milliseconds apart, until one side tops out.
+    Versus(u32),
+}
+
+impl Mode {
+    pub fn name(self) -> String {
+        match self {
+            Mode::Marathon => "Marathon".to_string(),
+            Mode::Sprint(lines) => format!("Sprint {}", lines),
+            Mode::Ultra => "Ultra".to_string(),
+            Mode::Dig { rising: false } => "Dig".to_string(),
+            Mode::Dig { rising: true } => "Dig rising".to_string(),
+            Mode::Zen => "Zen".to_string(),
+            Mode::Sandbox => "Sandbox".to_string(),
+            Mode::Versus(_) => "Versus".to_string(),
+        }
+    }
+
+    // The rules for a game in this mode, with the options picked in
+    // the settings.
+    pub fn rules(self, options: PlayOptions) -> Rules {
+        let rules = Rules {
+            gravity: options.gravity,
+            all_spin: options.all_spin,
+            size: options.size,
+            ..Rules::default()
+        };
+        match self {
+            Mode::Marathon | Mode::Versus(_) => rules,
+            Mode::Sprint(lines) => Rules {
+                line_goal: Some(lines),
+                ..rules
+            },
+            Mode::Ultra => Rules {
+                time_limit: Some(ULTRA_TIME),
+                ..rules
+            },
+            Mode::Dig { rising } => Rules {
+                garbage_rows: DIG_ROWS,
+                garbage_every: if rising { Some(DIG_RISE_TIME) } else { None },
+                clear_garbage: true,
+                ..rules
+            },
+            // A row a second, whatever the settings, and it's all
+            // practice
+            Mode::Zen => Rules {
+                gravity: Gravity::Fixed(1.0 / FRAMES_PER_SECOND),
+                top_out: TopOut::ClearBoard,
+                undo: true,
+                ..rules
+            },
+            Mode::Sandbox => Rules {
+                undo: true,
+                ..rules
+            },
+        }
+    }
+
+    // How games in this mode are scored, unless the settings say
+    // otherwise.  Marathon keeps the NES scoring it always had.
+    pub fn scoring(self) -> Scoring {
+        match self {
+            Mode::Marathon => Scoring::Nes,
+            _ => Scoring::Guideline,
+        }
+    }
+
+    // Whether a game counts for this mode's high score table.  Only
+    // games played with the mode's own rules and scoring and the
+    // standard pieces do, changing any of them in the settings makes
+    // it a different game.
+    pub fn ranks(self, rules: &Rules, scoring: Scoring, pieces: &PieceSet) -> bool {
+        *rules == self.rules(PlayOptions::default())
+            && scoring == self.scoring()
+            && *pieces == PieceSet::default()
+    }
+
+    // The high score table for games in this mode.  Zen games don't
+    // end, sandbox games start wherever and versus games are won or
+    // lost, so they don't have one.
+    pub fn table(self) -> Option<Table> {
+        match self {
+            Mode::Marathon => Some(MARATHON),
+            Mode::Sprint(20) => Some(SPRINT_20),
+            Mode::Sprint(100) => Some(SPRINT_100),
+            Mode::Sprint(_) => Some(SPRINT_40),
+            Mode::Ultra => Some(ULTRA),
+            Mode::Dig { rising: false } => Some(DIG),
+            Mode::Dig { rising: true } => Some(DIG_RISING),
+            Mode::Zen | Mode::Sandbox | Mode::Versus(_) => None,
+        }
+    }
+}
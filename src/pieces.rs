@@ -0,0 +1,694 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{Size, MAX_COLUMNS, MAX_ROWS};
+use crate::engine::Piece;
+use crate::layout::{PREVIEW_COLUMNS, PREVIEW_ROWS};
+use crate::spin::Kicks;
+
+// The shapes of the pieces, and where they come in.  Everything about
+// what a piece looks like comes from here: collision, plotting it on
+// the board, the spawn position and the previews.  The pieces come in
+// sets: the standard tetrominoes, pentominoes and a beginners' set of
+// smaller pieces are built in, and another can be read from a file.
+
+// The largest box a piece can turn in, big enough for a pentomino.
+pub const BOX: usize = 5;
+
+// A piece's shape in each of its four rotations, turning clockwise.
+// Each rotation is a mask for each row of the piece's box, bit 0 for
+// the leftmost column.
+pub type Shape = [[u8; BOX]; 4];
+
+// Which piece of its set a piece is, by its place in the set.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PieceType(pub u8);
+
+// The built in sets, each piece as its name and its first rotation.
+// The other rotations are turned from it, within a square box as big
+// as the rows are long or as many as there are, whichever is more.
+// That gives the Super Rotation System for the tetrominoes.
+const TETROMINOES: [(&str, &[&str]); 7] = [
+    ("I", &["....", "####"]),
+    ("O", &["##", "##"]),
+    ("T", &[".#.", "###"]),
+    ("J", &["#..", "###"]),
+    ("L", &["..#", "###"]),
+    ("S", &[".##", "##."]),
+    ("Z", &["##.", ".##"]),
+];
+
+// The eighteen one sided pentominoes, mirror images marked with '.
+const PENTOMINOES: [(&str, &[&str]); 18] = [
+    ("F", &[".##", "##.", ".#."]),
+    ("F'", &["##.", ".##", ".#."]),
+    ("I", &[".....", ".....", "#####"]),
+    ("L", &["...#", "####"]),
+    ("L'", &["#...", "####"]),
+    ("N", &["..##", "###."]),
+    ("N'", &["##..", ".###"]),
+    ("P", &["##.", "##.", "#.."]),
+    ("P'", &[".##", ".##", "..#"]),
+    ("T", &["###", ".#.", ".#."]),
+    ("U", &["#.#", "###"]),
+    ("V", &["#..", "#..", "###"]),
+    ("W", &["#..", "##.", ".##"]),
+    ("X", &[".#.", "###", ".#."]),
+    ("Y", &["..#.", "####"]),
+    ("Y'", &[".#..", "####"]),
+    ("Z", &["##.", ".#.", ".##"]),
+    ("Z'", &[".##", ".#.", "##."]),
+];
+
+// Small pieces for learning on.
+const BEGINNER: [(&str, &[&str]); 4] = [
+    ("Dot", &["#"]),
+    ("Domino", &["##"]),
+    ("I", &["...", "###"]),
+    ("L", &["#.", "##"]),
+];
+
+// Which set of pieces to play with.  The custom one is read from a
+// file.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Pieces {
+    Tetrominoes,
+    Pentominoes,
+    Beginner,
+    Custom,
+}
+
+impl Pieces {
+    pub fn name(self) -> String {
+        match self {
+            Pieces::Tetrominoes => "Tetrominoes".to_string(),
+            Pieces::Pentominoes => "Pentominoes".to_string(),
+            Pieces::Beginner => "Beginner".to_string(),
+            Pieces::Custom => "Custom".to_string(),
+        }
+    }
+
+    // Make the set, using the custom one if there is one.
+    pub fn set(self, custom: Option<&PieceSet>) -> PieceSet {
+        let built_in = |pieces: &[(&str, &[&str])]| {
+            let defs = pieces
+                .iter()
+                .map(|(name, rows)| PieceDef::turned(name, &strings(rows)).unwrap())
+                .collect();
+            PieceSet::new(self.name(), defs)
+        };
+        match (self, custom) {
+            (Pieces::Pentominoes, _) => built_in(&PENTOMINOES),
+            (Pieces::Beginner, _) => built_in(&BEGINNER),
+            (Pieces::Custom, Some(set)) => set.clone(),
+            _ => built_in(&TETROMINOES),
+        }
+    }
+}
+
+fn strings(rows: &[&str]) -> Vec<String> {
+    rows.iter().map(|row| row.to_string()).collect()
+}
+
+// Where a piece's box goes, on the board or in a preview, and which
+// way round it is.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Placement {
+    pub x: usize,
+    pub y: usize,
+    #[serde(default)]
+    pub rotation: u8,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct PieceDef {
+    name: String,
+    shape: Shape,
+    // Where the piece comes in, if not worked out from the board size.
+    spawn: Option<Placement>,
+    preview: Placement,
+    kicks: Kicks,
+    // The piece comes up this many times as often as a piece with a
+    // count of one.
+    count: u32,
+}
+
+// Turn the rows of a rotation into masks, checking it fits the box.
+fn parse_rotation(rows: &[String]) -> Result<[u8; BOX], String> {
+    if rows.len() > BOX {
+        return Err(format!("more than {} rows", BOX));
+    }
+    let mut masks = [0u8; BOX];
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() > BOX {
+            return Err(format!("row \"{}\" is wider than {}", row, BOX));
+        }
+        for (x, c) in row.chars().enumerate() {
+            if c != '.' && c != ' ' {
+                masks[y] |= 1 << x;
+            }
+        }
+    }
+    if masks.iter().all(|&mask| mask == 0) {
+        return Err("no squares".to_string());
+    }
+    Ok(masks)
+}
+
+// The size of the square box the rows turn in.
+fn box_size(rows: &[String]) -> usize {
+    let width = rows
+        .iter()
+        .map(|row| row.chars().count())
+        .max()
+        .unwrap_or(0);
+    rows.len().max(width)
+}
+
+// The rows turned a quarter clockwise within a box of the given size.
+fn turn(rows: [u8; BOX], size: usize) -> [u8; BOX] {
+    let mut turned = [0u8; BOX];
+    for (y, &mask) in rows.iter().enumerate().take(size) {
+        for (x, row) in turned.iter_mut().enumerate().take(size) {
+            if mask & (1 << x) != 0 {
+                *row |= 1 << (size - 1 - y);
+            }
+        }
+    }
+    turned
+}
+
+// The leftmost and rightmost columns, and the top and bottom rows, of
+// the squares in a rotation.
+fn extent(rows: [u8; BOX]) -> (usize, usize, usize, usize) {
+    let all = rows.iter().fold(0, |all, &mask| all | mask);
+    let left = all.trailing_zeros() as usize;
+    let right = 7 - all.leading_zeros() as usize;
+    let top = rows.iter().position(|&mask| mask != 0).unwrap_or(0);
+    let bottom = rows.iter().rposition(|&mask| mask != 0).unwrap_or(0);
+    (left, right, top, bottom)
+}
+
+// Check that a piece placed here, turned as it says, keeps its squares
+// inside a box of this many columns and rows.
+fn check_placement(
+    shape: &Shape,
+    at: Placement,
+    columns: usize,
+    rows: usize,
+) -> Result<(), String> {
+    let (_, right, _, bottom) = extent(shape[(at.rotation % 4) as usize]);
+    if at.x >= columns || at.x + right >= columns || at.y >= rows || at.y + bottom >= rows {
+        return Err(format!(
+            "{},{} is outside the {} by {} box",
+            at.x, at.y, columns, rows
+        ));
+    }
+    Ok(())
+}
+
+impl PieceDef {
+    // A piece from its rotations.  With only one the others are
+    // turned from it, with two it flips between them.
+    fn new(name: &str, rotations: &[Vec<String>]) -> Result<PieceDef, String> {
+        let count = rotations.len();
+        if count == 0 || count > 4 {
+            return Err(format!("{} needs one to four rotations", name));
+        }
+        if count == 1 {
+            return PieceDef::turned(name, &rotations[0]);
+        }
+        let mut shape = [[0; BOX]; 4];
+        for (r, rows) in shape.iter_mut().enumerate() {
+            *rows = parse_rotation(&rotations[r % count])
+                .map_err(|e| format!("{} rotation {}: {}", name, r % count, e))?;
+        }
+        let size = rotations
+            .iter()
+            .map(|rows| box_size(rows))
+            .max()
+            .unwrap_or(0);
+        Ok(PieceDef::from_shape(name, shape, size))
+    }
+
+    // A piece from its first rotation, turning in its box.
+    fn turned(name: &str, rows: &[String]) -> Result<PieceDef, String> {
+        let first = parse_rotation(rows).map_err(|e| format!("{}: {}", name, e))?;
+        let size = box_size(rows);
+        let mut shape = [first; 4];
+        for r in 1..4 {
+            shape[r] = turn(shape[r - 1], size);
+        }
+        Ok(PieceDef::from_shape(name, shape, size))
+    }
+
+    // Pieces sit in the middle of the preview box as they are first
+    // drawn.  Small boxes need no kicks and the long ones kick like
+    // the I.
+    fn from_shape(name: &str, shape: Shape, size: usize) -> PieceDef {
+        let (left, right, top, bottom) = extent(shape[0]);
+        let width = right - left + 1;
+        let height = bottom - top + 1;
+        PieceDef {
+            name: name.to_string(),
+            shape,
+            spawn: None,
+            preview: Placement {
+                x: ((BOX - width) / 2).saturating_sub(left),
+                y: (4usize.saturating_sub(height) / 2).saturating_sub(top),
+                rotation: 0,
+            },
+            kicks: match size {
+                0..=2 => Kicks::None,
+                3 => Kicks::Standard,
+                _ => Kicks::Long,
+            },
+            count: 1,
+        }
+    }
+
+    fn squares(&self) -> u32 {
+        self.shape[0].iter().map(|mask| mask.count_ones()).sum()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PieceSet {
+    pub name: String,
+    defs: Vec<PieceDef>,
+    // The playfield the pieces come in on.
+    size: Size,
+}
+
+impl Default for PieceSet {
+    fn default() -> PieceSet {
+        Pieces::Tetrominoes.set(None)
+    }
+}
+
+// A piece set file: a name and the pieces in it.  Each rotation is
+// rows of up to five characters, with `.` or a space for an empty
+// square and anything else for a filled one, like `["....", "####"]`.
+// Give one rotation to have the others turned from it, two for a
+// piece that flips between them, or all four.  Leave out the spawn or
+// preview place to have it worked out, and give a piece a count to
+// have it come up that many times as often.
+#[derive(Deserialize)]
+struct PieceFile {
+    name: String,
+    pieces: Vec<PieceEntry>,
+}
+
+#[derive(Deserialize)]
+struct PieceEntry {
+    name: String,
+    rotations: Vec<Vec<String>>,
+    spawn: Option<Placement>,
+    preview: Option<Placement>,
+    count: Option<u32>,
+}
+
+impl PieceSet {
+    fn new(name: String, defs: Vec<PieceDef>) -> PieceSet {
+        PieceSet {
+            name,
+            defs,
+            size: Size::default(),
+        }
+    }
+
+    // Read a piece set.  Returns none, after saying why, if the file
+    // is there but can't be used.
+    pub fn load(path: &Path) -> Option<PieceSet> {
+        let text = fs::read_to_string(path).ok()?;
+        let result = serde_json::from_str(&text)
+            .map_err(|e| e.to_string())
+            .and_then(PieceSet::from_file);
+        match result {
+            Ok(set) => Some(set),
+            Err(e) => {
+                println!("Unable to read {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn from_file(file: PieceFile) -> Result<PieceSet, String> {
+        if file.pieces.is_empty() || file.pieces.len() > u8::MAX as usize {
+            return Err("there must be between 1 and 255 pieces".to_string());
+        }
+        let mut defs = Vec::new();
+        for entry in file.pieces.iter() {
+            let mut def = PieceDef::new(&entry.name, &entry.rotations)?;
+            // Pieces are checked against the board when they move, but
+            // where they come in and where they are shown are taken as
+            // given, so they have to be in range.  The spawn is checked
+            // again against the board in use when the set is sized.
+            // The preview boards are BOX square, with the top
+            // PREVIEW_ROWS of them shown.
+            if let Some(spawn) = entry.spawn {
+                check_placement(&def.shape, spawn, MAX_COLUMNS, MAX_ROWS)
+                    .map_err(|e| format!("{} spawn: {}", entry.name, e))?;
+                def.spawn = Some(spawn);
+            }
+            if let Some(preview) = entry.preview {
+                let columns = PREVIEW_COLUMNS.min(BOX);
+                let rows = PREVIEW_ROWS.min(BOX);
+                check_placement(&def.shape, preview, columns, rows)
+                    .map_err(|e| format!("{} preview: {}", entry.name, e))?;
+                def.preview = preview;
+            }
+            def.count = entry.count.unwrap_or(1);
+            defs.push(def);
+        }
+        if defs.iter().all(|def| def.count == 0) {
+            return Err("every piece has a count of 0".to_string());
+        }
+        Ok(PieceSet::new(file.name, defs))
+    }
+
+    // The same pieces, coming in on a playfield of this size.  A set
+    // that can't come in on it is refused, after saying why, and the
+    // tetrominoes are used instead.
+    pub fn sized(self, size: Size) -> PieceSet {
+        match self.fits(size) {
+            Ok(()) => PieceSet { size, ..self },
+            Err(e) => {
+                println!("Unable to use the {} pieces: {}", self.name, e);
+                PieceSet {
+                    size,
+                    ..Pieces::Tetrominoes.set(None)
+                }
+            }
+        }
+    }
+
+    // Does every piece come in on the board for a playfield of this
+    // size?
+    pub fn fits(&self, size: Size) -> Result<(), String> {
+        if self.widest() > size.width {
+            return Err(format!(
+                "a piece is {} wide, the board only {}",
+                self.widest(),
+                size.width
+            ));
+        }
+        for def in self.defs.iter() {
+            if let Some(spawn) = def.spawn {
+                check_placement(&def.shape, spawn, size.columns(), size.rows())
+                    .map_err(|e| format!("{} spawn: {}", def.name, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    // The most columns any piece takes as it comes in, the narrowest
+    // the playfield can be.
+    pub fn widest(&self) -> usize {
+        self.defs
+            .iter()
+            .map(|def| {
+                let rotation = def.spawn.map_or(0, |at| at.rotation % 4);
+                let (left, right, _, _) = extent(def.shape[rotation as usize]);
+                right - left + 1
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.defs.len()
+    }
+
+    fn def(&self, tet_type: PieceType) -> &PieceDef {
+        &self.defs[tet_type.0 as usize % self.defs.len()]
+    }
+
+    pub fn name(&self, tet_type: PieceType) -> &str {
+        &self.def(tet_type).name
+    }
+
+    pub fn shape(&self, tet_type: PieceType) -> Shape {
+        self.def(tet_type).shape
+    }
+
+    pub fn kicks(&self, tet_type: PieceType) -> Kicks {
+        self.def(tet_type).kicks
+    }
+
+    // The T tetromino is spun by the three corner rule.
+    pub fn is_t(&self, tet_type: PieceType) -> bool {
+        let def = self.def(tet_type);
+        def.name == "T" && def.squares() == 4
+    }
+
+    // The piece with this name, if the set has one.
+    pub fn find(&self, name: &str) -> Option<PieceType> {
+        self.defs
+            .iter()
+            .position(|def| def.name == name)
+            .map(|i| PieceType(i as u8))
+    }
+
+    // The tetromino with this name, if the set has one.
+    pub fn tetromino(&self, name: &str) -> Option<PieceType> {
+        self.defs
+            .iter()
+            .position(|def| def.name == name && def.squares() == 4)
+            .map(|i| PieceType(i as u8))
+    }
+
+    // What goes in the randomizer bag, each piece as many times as its
+    // count.
+    pub fn bag(&self) -> Vec<PieceType> {
+        let mut bag = Vec::new();
+        for (i, def) in self.defs.iter().enumerate() {
+            for _ in 0..def.count {
+                bag.push(PieceType(i as u8));
+            }
+        }
+        bag
+    }
+
+    fn place(&self, tet_type: PieceType, at: Placement) -> Piece {
+        Piece {
+            tet_type,
+            rotation: at.rotation,
+            x: at.x,
+            y: at.y,
+            shape: self.shape(tet_type),
+        }
+    }
+
+    // Where a piece comes in at the top of the board.  Unless the set
+    // says otherwise that is as it is first drawn, in the middle of
+    // the playfield, rounding left, with its lowest squares on the row
+    // just above the ones shown.  The game drops it a row straight
+    // away if it can.  Without hidden rows it comes in on the third
+    // row shown instead.
+    pub fn spawn(&self, tet_type: PieceType) -> Piece {
+        let def = self.def(tet_type);
+        let at = def.spawn.unwrap_or_else(|| {
+            let (left, right, _, bottom) = extent(def.shape[0]);
+            let width = right - left + 1;
+            let lowest = match self.size.hidden {
+                0 => 2,
+                hidden => hidden - 1,
+            };
+            Placement {
+                x: (2 + self.size.width.saturating_sub(width) / 2).saturating_sub(left),
+                y: lowest.saturating_sub(bottom),
+                rotation: 0,
+            }
+        });
+        self.place(tet_type, at)
+    }
+
+    // Where a piece is drawn in the next and hold boxes.
+    pub fn preview(&self, tet_type: PieceType) -> Piece {
+        self.place(tet_type, self.def(tet_type).preview)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spin::kicks;
+
+    // The tetrominoes as the game first drew them, square by square,
+    // before the shapes came from a table.
+    const ORIGINAL: [(&str, [&[&str]; 4]); 7] = [
+        (
+            "I",
+            [
+                &["....", "####"],
+                &["..#", "..#", "..#", "..#"],
+                &["", "", "####"],
+                &[".#", ".#", ".#", ".#"],
+            ],
+        ),
+        (
+            "O",
+            [&["##", "##"], &["##", "##"], &["##", "##"], &["##", "##"]],
+        ),
+        (
+            "T",
+            [
+                &[".#.", "###"],
+                &[".#", ".##", ".#"],
+                &["", "###", ".#."],
+                &[".#", "##", ".#"],
+            ],
+        ),
+        (
+            "J",
+            [
+                &["#..", "###"],
+                &[".##", ".#", ".#"],
+                &["", "###", "..#"],
+                &[".#", ".#", "##"],
+            ],
+        ),
+        (
+            "L",
+            [
+                &["..#", "###"],
+                &[".#", ".#", ".##"],
+                &["", "###", "#.."],
+                &["##", ".#", ".#"],
+            ],
+        ),
+        (
+            "S",
+            [
+                &[".##", "##."],
+                &[".#", ".##", "..#"],
+                &["", ".##", "##."],
+                &["#", "##", ".#"],
+            ],
+        ),
+        (
+            "Z",
+            [
+                &["##.", ".##"],
+                &["..#", ".##", ".#"],
+                &["", "##.", ".##"],
+                &[".#", "##", "#"],
+            ],
+        ),
+    ];
+
+    // The guideline's SRS kicks for turning clockwise out of each
+    // rotation, with y going up the board the way the guideline
+    // writes them.
+    const SRS_KICKS: [[(i32, i32); 5]; 4] = [
+        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+        [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    ];
+    const SRS_I_KICKS: [[(i32, i32); 5]; 4] = [
+        [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+        [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+        [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+        [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    ];
+
+    #[test]
+    fn tetrominoes_match_the_original_shapes() {
+        let set = Pieces::Tetrominoes.set(None);
+        for (name, rotations) in ORIGINAL.iter() {
+            let shape = set.shape(set.find(name).unwrap());
+            for (r, rows) in rotations.iter().enumerate() {
+                let expected = parse_rotation(&strings(rows)).unwrap();
+                assert_eq!(shape[r], expected, "{} rotation {}", name, r);
+            }
+        }
+    }
+
+    #[test]
+    fn tetrominoes_kick_like_srs() {
+        let set = Pieces::Tetrominoes.set(None);
+        for (name, _) in ORIGINAL.iter() {
+            let tet_type = set.find(name).unwrap();
+            for rotation in 0..4 {
+                let piece = Piece {
+                    rotation,
+                    ..set.spawn(tet_type)
+                };
+                let expected: Vec<(i32, i32)> = match *name {
+                    "O" => vec![(0, 0)],
+                    "I" => SRS_I_KICKS[rotation as usize].to_vec(),
+                    _ => SRS_KICKS[rotation as usize].to_vec(),
+                };
+                let expected: Vec<(i32, i32)> = expected.iter().map(|&(x, y)| (x, -y)).collect();
+                let got = kicks(piece, set.kicks(tet_type));
+                assert_eq!(got, &expected[..], "{} from rotation {}", name, rotation);
+            }
+        }
+    }
+
+    // A set with just the I, placed as given.
+    fn one_piece(spawn: Option<Placement>, preview: Option<Placement>) -> PieceFile {
+        PieceFile {
+            name: "Test".to_string(),
+            pieces: vec![PieceEntry {
+                name: "I".to_string(),
+                rotations: vec![vec!["####".to_string()]],
+                spawn,
+                preview,
+                count: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn placements_must_be_in_range() {
+        let at = |x, y, rotation| Some(Placement { x, y, rotation });
+        assert!(PieceSet::from_file(one_piece(None, None)).is_ok());
+        assert!(PieceSet::from_file(one_piece(at(4, 20, 0), at(0, 1, 0))).is_ok());
+        assert!(PieceSet::from_file(one_piece(at(usize::MAX, 0, 0), None)).is_err());
+        assert!(PieceSet::from_file(one_piece(at(MAX_COLUMNS - 3, 0, 0), None)).is_err());
+        assert!(PieceSet::from_file(one_piece(at(4, MAX_ROWS, 0), None)).is_err());
+        assert!(PieceSet::from_file(one_piece(None, at(2, 0, 0))).is_err());
+        // Standing on end it only fits at the top of the preview
+        assert!(PieceSet::from_file(one_piece(None, at(0, 0, 1))).is_ok());
+        assert!(PieceSet::from_file(one_piece(None, at(0, 1, 1))).is_err());
+    }
+
+    #[test]
+    fn spawn_must_fit_the_board() {
+        let at = |x, y| Some(Placement { x, y, rotation: 0 });
+        // Below the default board, and past its right wall
+        let low = PieceSet::from_file(one_piece(at(4, 50), None)).unwrap();
+        assert!(low.fits(Size::default()).is_err());
+        let wide = PieceSet::from_file(one_piece(at(12, 0), None)).unwrap();
+        assert!(wide.fits(Size::default()).is_err());
+        let size = Size {
+            width: 20,
+            ..Size::default()
+        };
+        assert!(wide.fits(size).is_ok());
+        assert_eq!(wide.clone().sized(size).len(), 1);
+
+        // Refused, it is the tetrominoes instead
+        let set = low.sized(Size::default());
+        assert_eq!(set.len(), 7);
+        assert!(set.find("T").is_some());
+    }
+
+    #[test]
+    fn board_must_be_as_wide_as_the_pieces() {
+        let narrow = Size {
+            width: 4,
+            ..Size::default()
+        };
+        assert_eq!(Pieces::Tetrominoes.set(None).widest(), 4);
+        assert_eq!(Pieces::Pentominoes.set(None).widest(), 5);
+        assert!(Pieces::Tetrominoes.set(None).fits(narrow).is_ok());
+        assert!(Pieces::Pentominoes.set(None).fits(narrow).is_err());
+        assert_eq!(Pieces::Pentominoes.set(None).sized(narrow).len(), 7);
+    }
+}
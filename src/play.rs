@@ -0,0 +1,513 @@
+use std::time::Duration;
+
+use ggez::event::KeyCode;
+use ggez::graphics;
+use ggez::graphics::DrawParam;
+use ggez::nalgebra::Point2;
+use ggez::timer;
+use ggez::{filesystem, Context, GameResult};
+
+use crate::animation::Animator;
+use crate::bot::Bot;
+use crate::diagram::Diagram;
+use crate::engine::{plot_tet, Action, Board, BoardState, Game, TileType};
+use crate::event::{ClearKind, GameEvent, Logger, Subscriber};
+use crate::highscores::{format_time, Unit};
+use crate::menus::{GameOverScene, NameEntryScene, PauseScene, Results};
+use crate::mode::Mode;
+use crate::pieces::BOX;
+use crate::save::{SavedGame, Saving};
+use crate::scene::{Input, Scene, Shared, Transition};
+use crate::scoring::Scoring;
+use crate::spin::spin_name;
+use crate::stats::Stats;
+use crate::versus::Versus;
+use crate::{
+    draw_board_contents, draw_board_grid, draw_preview_contents, draw_preview_grid, draw_text,
+};
+
+// How long the finished board stays up before the results come in.
+const GAME_OVER_DELAY: Duration = Duration::from_millis(1500);
+
+// How long the name of a special clear, like "T-Spin Double", stays up.
+const CALLOUT_TIME: Duration = Duration::from_secs(2);
+
+// The time between the bot's moves in the demo.
+const DEMO_DELAY: Duration = Duration::from_millis(100);
+
+// The subscribers every game starts with: only the logger, if it is
+// turned on.
+fn subscribers() -> Vec<Box<dyn Subscriber>> {
+    let mut subscribers: Vec<Box<dyn Subscriber>> = Vec::new();
+    if let Some(logger) = Logger::from_env() {
+        subscribers.push(Box::new(logger));
+    }
+    subscribers
+}
+
+// A game being played.
+pub struct PlayScene {
+    mode: Mode,
+    game: Game,
+    // Which scoring system the game has, for saving it.
+    scoring: Scoring,
+    // Everything that hears about game events.  The ones we need to
+    // talk to directly have their own fields, anything else can be
+    // added to the list of subscribers.
+    animator: Animator,
+    stats: Stats,
+    subscribers: Vec<Box<dyn Subscriber>>,
+    // When the game ended.
+    over_at: Option<Duration>,
+    // Whether the goal was reached, rather than the pieces reaching
+    // the top.
+    finished: bool,
+    // The sprint split times so far, and those of the best time to
+    // compare against.
+    splits: Vec<u64>,
+    best_splits: Vec<u64>,
+    // The last special clear and when it happened.
+    callout: Option<(String, Duration)>,
+    // The bot playing this game, for the demo, and the bot's own game
+    // in versus.
+    bot: Option<Bot>,
+    versus: Option<Versus>,
+}
+
+impl PlayScene {
+    pub fn new(shared: &mut Shared, mode: Mode) -> PlayScene {
+        shared.audio.start_music();
+        let scoring = shared.options.scoring.unwrap_or_else(|| mode.scoring());
+        let pieces = shared.options.pieces.set(shared.piece_set.as_ref());
+        let rules = mode.rules(shared.options);
+        shared.layout = shared.layout.with_size(rules.size);
+        let best_splits = mode
+            .table()
+            .and_then(|table| shared.high_scores.entries(table).first())
+            .map_or(Vec::new(), |best| best.splits.clone());
+        let seed = rand::random();
+        let mut game = Game::new(
+            seed,
+            rules,
+            scoring.system(shared.score_table.as_ref()),
+            pieces.clone(),
+        );
+        if mode == Mode::Sandbox {
+            if let Some(setup) = &shared.setup {
+                setup.apply(&mut game);
+            }
+        }
+        // The bot gets the same pieces in the same order
+        let versus = match mode {
+            Mode::Versus(delay) => {
+                let bot_game = Game::new(
+                    seed,
+                    rules,
+                    scoring.system(shared.score_table.as_ref()),
+                    pieces,
+                );
+                let bot = Bot::new(shared.bot_weights, Duration::from_millis(delay.into()));
+                Some(Versus::new(bot_game, bot))
+            }
+            _ => None,
+        };
+        PlayScene {
+            mode,
+            game,
+            scoring,
+            animator: Animator::new(shared.animations),
+            stats: Stats::default(),
+            subscribers: subscribers(),
+            over_at: None,
+            finished: false,
+            splits: Vec::new(),
+            best_splits,
+            callout: None,
+            bot: None,
+            versus,
+        }
+    }
+
+    // The bot playing marathon on its own, to watch from the title
+    // screen.
+    pub fn demo(shared: &mut Shared) -> PlayScene {
+        let mut scene = PlayScene::new(shared, Mode::Marathon);
+        scene.bot = Some(Bot::new(shared.bot_weights, DEMO_DELAY));
+        scene
+    }
+
+    // Carry on with a saved game.  Its custom scoring table is the one
+    // it was saved with, even if the file has changed since.
+    pub fn resume(shared: &mut Shared, saved: SavedGame) -> PlayScene {
+        shared.audio.start_music();
+        let mut game = saved.game;
+        game.scoring = saved.scoring.system(saved.score_table.as_ref());
+        shared.layout = shared.layout.with_size(game.rules.size);
+        PlayScene {
+            mode: saved.mode,
+            game,
+            scoring: saved.scoring,
+            animator: Animator::new(shared.animations),
+            stats: saved.stats,
+            subscribers: subscribers(),
+            over_at: None,
+            finished: false,
+            splits: saved.splits,
+            best_splits: saved.best_splits,
+            callout: None,
+            bot: None,
+            versus: None,
+        }
+    }
+
+    // Save the game to carry on with later, unless it's already over.
+    // The demo isn't worth keeping, and the bot's side of a versus
+    // game isn't saved, so neither are kept.
+    fn save(&self, ctx: &Context, shared: &Shared) {
+        if self.over_at.is_some() || self.bot.is_some() || self.versus.is_some() {
+            return;
+        }
+        let saving = Saving {
+            mode: self.mode,
+            scoring: self.scoring,
+            score_table: shared.score_table.as_ref(),
+            game: &self.game,
+            stats: &self.stats,
+            splits: &self.splits,
+            best_splits: &self.best_splits,
+        };
+        let dir = filesystem::user_data_dir(ctx);
+        match saving.save(dir) {
+            Ok(()) => println!("Saved the game in {}", dir.display()),
+            Err(e) => println!("Unable to save the game: {}", e),
+        }
+    }
+
+    // Pass along everything the game did since the last call.
+    fn dispatch_events(&mut self, shared: &mut Shared, now: Duration) {
+        for event in self.game.take_events() {
+            match &event {
+                GameEvent::GameOver { reason, .. } => {
+                    self.over_at = Some(now);
+                    self.callout = Some((reason.name().to_string(), now));
+                }
+                GameEvent::Finished { .. } => {
+                    self.over_at = Some(now);
+                    self.finished = true;
+                }
+                GameEvent::Split { time, .. } => self.splits.push(time.as_millis() as u64),
+                GameEvent::LinesCleared {
+                    kind: ClearKind::Tetris,
+                    ..
+                } => self.callout = Some(("Tetris".to_string(), now)),
+                GameEvent::Spun {
+                    name, spin, lines, ..
+                } => {
+                    self.callout = Some((spin_name(name, *spin, *lines), now));
+                }
+                GameEvent::PerfectClear { .. } => {
+                    self.callout = Some(("Perfect Clear!".to_string(), now));
+                }
+                _ => (),
+            }
+            if let Some(versus) = &mut self.versus {
+                let rows = versus.player_event(&event);
+                self.game.add_garbage(rows);
+            }
+            if let Some(bot) = &mut self.bot {
+                bot.notify(&event, now);
+            }
+            self.animator.notify(&event, now);
+            shared.audio.notify(&event, now);
+            self.stats.notify(&event, now);
+            for subscriber in self.subscribers.iter_mut() {
+                subscriber.notify(&event, now);
+            }
+        }
+    }
+
+    fn results(&self) -> Results {
+        // Races are ranked by time, everything else by score.
+        let value = if self.is_race() {
+            self.game.elapsed.as_millis() as u64
+        } else {
+            u64::from(self.game.score)
+        };
+        Results {
+            mode: self.mode,
+            finished: self.finished,
+            value,
+            seed: self.game.seed,
+            ranked: self
+                .mode
+                .ranks(&self.game.rules, self.scoring, &self.game.pieces),
+            splits: self.splits.clone(),
+            best_splits: self.best_splits.clone(),
+            score: self.game.score,
+            level: self.game.level,
+            lines: self.game.lines,
+            pieces: self.stats.total_pieces(),
+            tetrises: self.stats.tetrises,
+            t_spins: self.stats.total_t_spins(),
+            max_combo: self.stats.max_combo,
+            perfect_clears: self.stats.perfect_clears,
+            undos: self.game.undos,
+        }
+    }
+
+    // Whether the game is a race to a goal, ranked by time.
+    fn is_race(&self) -> bool {
+        self.mode.table().map(|table| table.unit) == Some(Unit::Millis)
+    }
+
+    // The debug keys, for trying things out.  Moving pieces about and
+    // changing them is done in the editor.
+    fn debug_key(&mut self, keycode: KeyCode) {
+        let game = &mut self.game;
+        match keycode {
+            KeyCode::Z => {
+                print!("{}", Diagram::of_game(game).to_text(&game.pieces));
+            }
+            KeyCode::Y if game.level > 1 => game.level -= 1,
+            KeyCode::U if game.level < 255 => game.level += 1,
+            _ => (),
+        }
+    }
+}
+
+// The split times, each with how far ahead or behind the best time
+// it is.
+pub fn split_lines(splits: &[u64], best: &[u64]) -> Vec<String> {
+    splits
+        .iter()
+        .enumerate()
+        .map(|(i, &millis)| {
+            let mut line = format!("{:>3}: {}", (i + 1) * 10, format_time(millis));
+            if let Some(&best) = best.get(i) {
+                let (sign, diff) = if millis <= best {
+                    ('-', best - millis)
+                } else {
+                    ('+', millis - best)
+                };
+                line.push_str(&format!(" {}{}.{:03}", sign, diff / 1000, diff % 1000));
+            }
+            line
+        })
+        .collect()
+}
+
+impl Scene for PlayScene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        // Saving from the pause menu
+        if shared.save_and_quit {
+            shared.save_and_quit = false;
+            self.save(ctx, shared);
+            shared.audio.stop_music();
+            return Ok(Transition::Home);
+        }
+        // Coming back from the pause menu
+        if self.game.board_state == BoardState::Paused {
+            self.game.apply(Action::Pause);
+        }
+        self.animator.settings = shared.animations;
+
+        let now = timer::time_since_start(ctx);
+        let dt = timer::delta(ctx);
+        // Once one side of a versus game has lost, both stop
+        if self.over_at.is_none() {
+            if let Some(bot) = &mut self.bot {
+                bot.play(&mut self.game, dt);
+            }
+            self.game.advance(dt);
+            // Leave the full rows in place until their animation is done
+            if !(self.game.board_state == BoardState::Clearing && self.animator.clearing_lines(now))
+            {
+                self.game.fall(dt);
+            }
+            if let Some(versus) = &mut self.versus {
+                versus.update(dt, now);
+                if versus.is_over() {
+                    self.over_at = Some(now);
+                    self.finished = true;
+                    self.callout = Some(("You win!".to_string(), now));
+                }
+            }
+        }
+        self.dispatch_events(shared, now);
+
+        // Once the game is over, let the board sit for a moment, then
+        // on to the results, by way of the high score table if the
+        // score is good enough.
+        if let Some(over_at) = self.over_at {
+            if now - over_at >= GAME_OVER_DELAY {
+                // The demo goes back to the title screen
+                if self.bot.is_some() {
+                    shared.audio.stop_music();
+                    return Ok(Transition::Pop);
+                }
+                let results = self.results();
+                // A race only counts if it reaches the goal, and nothing
+                // counts once the settings changed the game
+                let counts = results.ranked && (results.finished || !self.is_race());
+                let high_score = match results.mode.table() {
+                    Some(table) => {
+                        counts
+                            && results.value > 0
+                            && shared.high_scores.qualifies(table, results.value)
+                    }
+                    None => false,
+                };
+                let next: Box<dyn Scene> = if high_score {
+                    Box::new(NameEntryScene::new(results))
+                } else {
+                    Box::new(GameOverScene::new(results, false))
+                };
+                return Ok(Transition::Replace(next));
+            }
+        }
+        Ok(Transition::None)
+    }
+
+    fn input(&mut self, ctx: &mut Context, shared: &mut Shared, input: Input) -> Transition {
+        // Anything but moving the mouse ends the demo
+        if self.bot.is_some() {
+            return match input {
+                Input::MouseMove(..) | Input::MouseUp(_) => Transition::None,
+                _ => {
+                    shared.audio.stop_music();
+                    Transition::Pop
+                }
+            };
+        }
+        if self.over_at.is_some() {
+            return Transition::None;
+        }
+        if input == Input::Key(KeyCode::Q) {
+            self.save(ctx, shared);
+            return Transition::Quit;
+        }
+        if input.is_pause() {
+            self.game.apply(Action::Pause);
+            self.dispatch_events(shared, timer::time_since_start(ctx));
+            return Transition::Push(Box::new(PauseScene::new()));
+        }
+        if let Some(action) = input.game_action() {
+            self.game.apply(action);
+        } else if let Input::Key(keycode) = input {
+            self.debug_key(keycode);
+        }
+        self.dispatch_events(shared, timer::time_since_start(ctx));
+        Transition::None
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult {
+        // Text, one line after another down the stats panel
+        let layout = shared.layout;
+        let game = &mut self.game;
+        let next = game.piece_queue.peek();
+        let mut lines = vec![
+            format!("Board state:{:#?}", game.board_state),
+            format!("Score:{} ({})", game.score, game.scoring.name()),
+            format!("Lines:{}", game.lines),
+            format!("Level:{}", game.level),
+            format!("Speed:{:.3}G", game.rows_per_frame()),
+            format!("Next: {:?}", next),
+            format!("Pieces:{}", self.stats.total_pieces()),
+            format!("Tetrises:{}", self.stats.tetrises),
+            format!("T-Spins:{}", self.stats.total_t_spins()),
+            format!("Perfect clears:{}", self.stats.perfect_clears),
+            format!("Combo:{}", game.combo.saturating_sub(1)),
+            format!("Back to back:{}", game.back_to_back.saturating_sub(1)),
+            format!("Sound:{}", if shared.audio.muted() { "off" } else { "on" }),
+        ];
+        if self.bot.is_some() {
+            lines.insert(0, "Demo, press any key".to_string());
+        }
+        if let Some(versus) = &self.versus {
+            lines.push(format!("Bot lines:{}", versus.game.lines));
+            lines.push(format!("Garbage coming:{}", versus.to_player));
+        }
+        let now = timer::time_since_start(ctx);
+        if let Some((callout, at)) = &self.callout {
+            if now - *at < CALLOUT_TIME {
+                lines.push(callout.clone());
+            }
+        }
+        if let Some(limit) = game.rules.time_limit {
+            let left = limit - game.elapsed.min(limit);
+            lines.push(format!(
+                "Time left:{}",
+                format_time(left.as_millis() as u64)
+            ));
+        }
+        if let Some(goal) = game.rules.line_goal {
+            lines.push(format!(
+                "Time:{}",
+                format_time(game.elapsed.as_millis() as u64)
+            ));
+            lines.push(format!("Lines left:{}", goal.saturating_sub(game.lines)));
+            lines.extend(split_lines(&self.splits, &self.best_splits));
+        }
+        if game.rules.clear_garbage {
+            lines.push(format!(
+                "Time:{}",
+                format_time(game.elapsed.as_millis() as u64)
+            ));
+            lines.push(format!("Garbage left:{}", game.garbage_left()));
+        }
+        for (i, line) in lines.iter().enumerate() {
+            let y = layout.stats.y + i as f32 * layout.line_height();
+            draw_text(ctx, &layout, line.clone(), (layout.stats.x, y))?;
+        }
+
+        // The board grid
+        // Horizional lines for the playfield
+        let mb = &mut graphics::MeshBuilder::new();
+
+        draw_board_grid(mb, &layout)?;
+        //
+        // Any board state except for clearing, we plot the tet at its
+        // current coordinates.  When clearing, the piece location has become
+        // base, so don't draw anything.
+        if game.board_state != BoardState::Clearing {
+            plot_tet(&mut game.board, game.piece, TileType::Tet);
+        }
+
+        draw_board_contents(mb, &layout, &game.board)?;
+
+        draw_preview_grid(mb, &layout, layout.next)?;
+        // When we add a game start button, we can do this less
+        // frequently.  The only time it needs to change is on start
+        // and when a piece has reached the bottom.  XXX
+        plot_tet(&mut game.next_board, game.next_piece, TileType::Tet);
+        draw_preview_contents(mb, &layout, layout.next, &game.next_board)?;
+
+        draw_preview_grid(mb, &layout, layout.hold)?;
+        if let Some(held) = game.held {
+            let mut hold_board = Board::new(BOX, BOX);
+            plot_tet(&mut hold_board, game.pieces.preview(held), TileType::Tet);
+            draw_preview_contents(mb, &layout, layout.hold, &hold_board)?;
+        }
+
+        // The bot's board, smaller, beside the player's
+        if let Some(versus) = &mut self.versus {
+            let inset = layout.inset(layout.side);
+            let bot_game = &mut versus.game;
+            draw_board_grid(mb, &inset)?;
+            if bot_game.board_state != BoardState::Clearing {
+                plot_tet(&mut bot_game.board, bot_game.piece, TileType::Tet);
+            }
+            draw_board_contents(mb, &inset, &bot_game.board)?;
+        }
+
+        self.animator.draw(mb, &layout, now)?;
+
+        let (dx, dy) = self.animator.shake_offset(&layout, now);
+        let m = mb.build(ctx)?;
+        graphics::draw(ctx, &m, DrawParam::new().dest(Point2::new(dx, dy)))
+    }
+
+    fn quit(&mut self, ctx: &mut Context, shared: &mut Shared) {
+        self.save(ctx, shared);
+    }
+}
@@ -0,0 +1,227 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::Game;
+use crate::highscores::set_aside;
+use crate::mode::Mode;
+use crate::scoring::{ScoreTable, Scoring};
+use crate::stats::Stats;
+
+// A game put aside to carry on with later.  There is only ever one,
+// saved in the data directory when a game is quit part way through,
+// and removed again once it is continued.
+
+const FILE_NAME: &str = "save.json";
+
+// The version of the file we write.  Bump this whenever a change to
+// the game state means an older save can't be read any more.
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile<T> {
+    version: u32,
+    #[serde(flatten)]
+    game: T,
+}
+
+// The game being played, borrowed to write it out.
+#[derive(Serialize)]
+pub struct Saving<'a> {
+    pub mode: Mode,
+    // The game's scoring system, which isn't saved with it, and the
+    // custom table if that is what it was using.
+    pub scoring: Scoring,
+    pub score_table: Option<&'a ScoreTable>,
+    pub game: &'a Game,
+    pub stats: &'a Stats,
+    pub splits: &'a [u64],
+    pub best_splits: &'a [u64],
+}
+
+impl<'a> Saving<'a> {
+    // Write the game out, the same careful way as the high scores.
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(FILE_NAME);
+        let file = SaveFile {
+            version: VERSION,
+            game: self,
+        };
+        let text = serde_json::to_string(&file)?;
+        let temp = path.with_extension("json.new");
+        fs::write(&temp, text)?;
+        fs::rename(&temp, path)
+    }
+}
+
+// The same again, read back in.
+#[derive(Deserialize)]
+pub struct SavedGame {
+    pub mode: Mode,
+    pub scoring: Scoring,
+    pub score_table: Option<ScoreTable>,
+    pub game: Game,
+    pub stats: Stats,
+    pub splits: Vec<u64>,
+    pub best_splits: Vec<u64>,
+}
+
+impl SavedGame {
+    // Is there a game waiting to be continued?
+    pub fn exists(dir: &Path) -> bool {
+        dir.join(FILE_NAME).is_file()
+    }
+
+    // A saved game from the text of its file.
+    pub fn from_text(text: &str) -> Result<SavedGame, String> {
+        // Check the version first, a save from another version might
+        // not read as a game at all.
+        let version = serde_json::from_str::<serde_json::Value>(text)
+            .ok()
+            .and_then(|value| value.get("version").and_then(|v| v.as_u64()));
+        if version != Some(u64::from(VERSION)) {
+            return Err(format!(
+                "it is version {:?}, we can only continue version {}",
+                version, VERSION
+            ));
+        }
+        serde_json::from_str::<SaveFile<SavedGame>>(text)
+            .map(|file| file.game)
+            .map_err(|e| e.to_string())
+    }
+
+    // Read the saved game, and remove it so it can only be continued
+    // once.  A file we can't read is set aside.
+    pub fn take(dir: &Path) -> Option<SavedGame> {
+        let path = dir.join(FILE_NAME);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                println!("Unable to read {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        match SavedGame::from_text(&text) {
+            Ok(saved) => {
+                if let Err(e) = fs::remove_file(&path) {
+                    println!("Unable to remove {}: {}", path.display(), e);
+                }
+                Some(saved)
+            }
+            Err(e) => {
+                println!("Unable to read {}: {}", path.display(), e);
+                set_aside(&path);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Action, BoardState, Rules};
+    use crate::pieces::PieceSet;
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+    use std::time::Duration;
+
+    // An empty directory of our own to keep a file in.
+    fn empty_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("ttet-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // A game a few pieces in.
+    fn played() -> Game {
+        let scoring = Scoring::Guideline.system(None);
+        let mut game = Game::new(42, Rules::default(), scoring, PieceSet::default());
+        for i in 0..6 {
+            let side = if i % 2 == 0 {
+                Action::MoveLeft
+            } else {
+                Action::MoveRight
+            };
+            game.apply(side);
+            game.apply(side);
+            game.apply(Action::HardDrop);
+            if game.board_state == BoardState::Clearing {
+                game.fall(Duration::from_secs(0));
+            }
+        }
+        game.advance(Duration::from_millis(12_345));
+        game
+    }
+
+    fn save(game: &Game, dir: &Path) {
+        let saving = Saving {
+            mode: Mode::Marathon,
+            scoring: Scoring::Guideline,
+            score_table: None,
+            game,
+            stats: &Stats::default(),
+            splits: &[],
+            best_splits: &[],
+        };
+        saving.save(dir).unwrap();
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = empty_dir("save-round-trip");
+        let mut game = played();
+        save(&game, &dir);
+        assert!(SavedGame::exists(&dir));
+        let mut loaded = SavedGame::take(&dir).unwrap();
+        // It can only be continued once
+        assert!(!SavedGame::exists(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.mode, Mode::Marathon);
+        assert_eq!(loaded.scoring, Scoring::Guideline);
+        let saved = &mut loaded.game;
+        assert_eq!(saved.board, game.board);
+        assert_eq!(saved.piece.tet_type, game.piece.tet_type);
+        assert_eq!((saved.piece.x, saved.piece.y), (game.piece.x, game.piece.y));
+        assert_eq!(saved.score, game.score);
+        assert_eq!(saved.lines, game.lines);
+        assert_eq!(saved.elapsed, game.elapsed);
+        assert_eq!(saved.piece_queue.coming(), game.piece_queue.coming());
+
+        // The random numbers carry on the same, for the pieces and for
+        // the holes in the garbage.
+        for _ in 0..30 {
+            assert_eq!(saved.piece_queue.next(), game.piece_queue.next());
+        }
+        saved.add_garbage(3);
+        game.add_garbage(3);
+        assert_eq!(saved.board, game.board);
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let dir = empty_dir("save-newer");
+        save(&played(), &dir);
+        let path = dir.join(FILE_NAME);
+        let text = fs::read_to_string(&path).unwrap();
+        let newer = text.replacen("\"version\":1", "\"version\":2", 1);
+        assert_ne!(newer, text);
+        assert!(SavedGame::from_text(&newer).is_err());
+
+        fs::write(&path, &newer).unwrap();
+        assert!(SavedGame::take(&dir).is_none());
+        // Kept to one side, not lost
+        assert!(!SavedGame::exists(&dir));
+        assert_eq!(
+            fs::read_to_string(dir.join("save.json.bad")).unwrap(),
+            newer
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
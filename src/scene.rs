@@ -0,0 +1,338 @@
+use ggez::conf::FullscreenType;
+use ggez::event::{Button, KeyCode, MouseButton};
+use ggez::graphics;
+use ggez::graphics::{Color, Rect, Scale, Text, TextFragment};
+use ggez::nalgebra::Point2;
+use ggez::{filesystem, Context, GameResult};
+
+use crate::animation::AnimationSettings;
+use crate::audio::Audio;
+use crate::board::Size;
+use crate::bot::Weights;
+use crate::editor::Setup;
+use crate::engine::Action;
+use crate::highscores::HighScores;
+use crate::layout::Layout;
+use crate::mode::PlayOptions;
+use crate::pieces::PieceSet;
+use crate::scoring::ScoreTable;
+
+// The program is a stack of scenes: the title screen at the bottom,
+// then menus and the game itself on top of it.  Only the top scene
+// gets input and updates.  Scenes that don't cover the whole screen,
+// like the pause menu, let the scene below them show through.
+
+// Input from the keyboard or a gamepad, handed to the top scene.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Input {
+    Key(KeyCode),
+    Button(Button),
+    // A typed character, for entering names.
+    Text(char),
+    // The mouse, in the same pixels we draw in: a button pressed or let
+    // go, or the pointer moving.
+    MouseDown(MouseButton, f32, f32),
+    MouseUp(MouseButton),
+    MouseMove(f32, f32),
+}
+
+// What menus respond to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MenuCommand {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+impl Input {
+    pub fn menu_command(self) -> Option<MenuCommand> {
+        match self {
+            Input::Key(KeyCode::Up) | Input::Key(KeyCode::W) => Some(MenuCommand::Up),
+            Input::Key(KeyCode::Down) | Input::Key(KeyCode::S) => Some(MenuCommand::Down),
+            Input::Key(KeyCode::Left) | Input::Key(KeyCode::A) => Some(MenuCommand::Left),
+            Input::Key(KeyCode::Right) | Input::Key(KeyCode::D) => Some(MenuCommand::Right),
+            Input::Key(KeyCode::Return) | Input::Key(KeyCode::Space) => Some(MenuCommand::Select),
+            Input::Key(KeyCode::Escape) | Input::Key(KeyCode::Back) => Some(MenuCommand::Back),
+            Input::Button(Button::DPadUp) => Some(MenuCommand::Up),
+            Input::Button(Button::DPadDown) => Some(MenuCommand::Down),
+            Input::Button(Button::DPadLeft) => Some(MenuCommand::Left),
+            Input::Button(Button::DPadRight) => Some(MenuCommand::Right),
+            Input::Button(Button::South) | Input::Button(Button::Start) => {
+                Some(MenuCommand::Select)
+            }
+            Input::Button(Button::East) => Some(MenuCommand::Back),
+            _ => None,
+        }
+    }
+
+    // The game action for each key or button.  Pausing is left out,
+    // that brings up the pause menu instead.
+    pub fn game_action(self) -> Option<Action> {
+        match self {
+            Input::Key(KeyCode::A) => Some(Action::MoveLeft),
+            Input::Key(KeyCode::D) => Some(Action::MoveRight),
+            Input::Key(KeyCode::W) => Some(Action::Rotate),
+            Input::Key(KeyCode::S) => Some(Action::SoftDrop),
+            Input::Key(KeyCode::Space) => Some(Action::HardDrop),
+            Input::Key(KeyCode::LShift) => Some(Action::Hold),
+            Input::Key(KeyCode::Back) => Some(Action::Undo),
+            Input::Button(Button::DPadLeft) => Some(Action::MoveLeft),
+            Input::Button(Button::DPadRight) => Some(Action::MoveRight),
+            Input::Button(Button::DPadDown) => Some(Action::SoftDrop),
+            Input::Button(Button::DPadUp) => Some(Action::HardDrop),
+            Input::Button(Button::South) | Input::Button(Button::East) => Some(Action::Rotate),
+            Input::Button(Button::LeftTrigger) | Input::Button(Button::RightTrigger) => {
+                Some(Action::Hold)
+            }
+            Input::Button(Button::Select) => Some(Action::Undo),
+            _ => None,
+        }
+    }
+
+    // The keys and buttons that bring up the pause menu.
+    pub fn is_pause(self) -> bool {
+        matches!(
+            self,
+            Input::Key(KeyCode::P) | Input::Key(KeyCode::Escape) | Input::Button(Button::Start)
+        )
+    }
+}
+
+// Everything that lives longer than one scene.
+pub struct Shared {
+    // Where everything goes on the screen
+    pub layout: Layout,
+    pub fullscreen: bool,
+    pub audio: Audio,
+    pub animations: AnimationSettings,
+    pub options: PlayOptions,
+    // The custom scoring table, if there is one in the data directory.
+    pub score_table: Option<ScoreTable>,
+    // A piece set to play with instead of the standard one, if there is
+    // one in the data directory.
+    pub piece_set: Option<PieceSet>,
+    pub high_scores: HighScores,
+    // How the bot weighs up the board, from bot.json in the data
+    // directory if there is one.
+    pub bot_weights: Weights,
+    // The last position built in the editor, to play again.
+    pub setup: Option<Setup>,
+    // Save and quit was picked from the pause menu, for the game
+    // underneath to do.
+    pub save_and_quit: bool,
+}
+
+impl Shared {
+    pub fn new(ctx: &mut Context) -> GameResult<Shared> {
+        let mut shared = Shared {
+            layout: Layout::new(1.0, 1.0, Size::default()),
+            fullscreen: false,
+            audio: Audio::new(ctx),
+            animations: AnimationSettings::default(),
+            options: PlayOptions::default(),
+            score_table: ScoreTable::load(&filesystem::user_data_dir(ctx).join("scoring.json")),
+            piece_set: PieceSet::load(&filesystem::user_data_dir(ctx).join("pieces.json")),
+            high_scores: HighScores::load(filesystem::user_data_dir(ctx)),
+            bot_weights: Weights::load(&filesystem::user_data_dir(ctx).join("bot.json"))
+                .unwrap_or_default(),
+            setup: None,
+            save_and_quit: false,
+        };
+        shared.update_layout(ctx)?;
+        Ok(shared)
+    }
+
+    // Recompute the layout for the current window size.  We draw in
+    // physical pixels, so on a HiDPI display the drawable size is
+    // larger than the window size and everything scales up with it.
+    pub fn update_layout(&mut self, ctx: &mut Context) -> GameResult {
+        let (width, height) = graphics::drawable_size(ctx);
+        graphics::set_screen_coordinates(ctx, Rect::new(0.0, 0.0, width, height))?;
+        self.layout = Layout::new(width, height, self.layout.size);
+        Ok(())
+    }
+
+    pub fn toggle_fullscreen(&mut self, ctx: &mut Context) -> GameResult {
+        self.fullscreen = !self.fullscreen;
+        let fullscreen_type = if self.fullscreen {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Windowed
+        };
+        graphics::set_fullscreen(ctx, fullscreen_type)?;
+        self.update_layout(ctx)
+    }
+}
+
+// What the top scene wants to happen next.
+pub enum Transition {
+    // Stay where we are.
+    None,
+    // Put a new scene on top.
+    Push(Box<dyn Scene>),
+    // Go back to the scene below.
+    Pop,
+    // Swap the top scene for another.
+    Replace(Box<dyn Scene>),
+    // Go all the way back to the bottom scene.
+    Home,
+    Quit,
+}
+
+pub trait Scene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition>;
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult;
+
+    fn input(&mut self, ctx: &mut Context, shared: &mut Shared, input: Input) -> Transition;
+
+    // Called instead of update while another scene is on top of this
+    // one.
+    fn covered_update(&mut self, _ctx: &mut Context) {}
+
+    // Called when the window is closed, for anything that should be
+    // kept.
+    fn quit(&mut self, _ctx: &mut Context, _shared: &mut Shared) {}
+
+    // Scenes that only cover part of the screen draw on top of the
+    // scene below.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    // Scenes that take typed text don't want any of the keys to be
+    // taken for other things, like muting the sound.
+    fn takes_text(&self) -> bool {
+        false
+    }
+}
+
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+}
+
+impl SceneStack {
+    pub fn new(first: Box<dyn Scene>) -> SceneStack {
+        SceneStack {
+            scenes: vec![first],
+        }
+    }
+
+    pub fn takes_text(&self) -> bool {
+        self.scenes.last().map(|s| s.takes_text()) == Some(true)
+    }
+
+    // Act on a transition.  Returns false once it's time to quit.
+    fn apply(&mut self, transition: Transition) -> bool {
+        match transition {
+            Transition::None => (),
+            Transition::Push(scene) => self.scenes.push(scene),
+            Transition::Pop => {
+                self.scenes.pop();
+            }
+            Transition::Replace(scene) => {
+                self.scenes.pop();
+                self.scenes.push(scene);
+            }
+            Transition::Home => self.scenes.truncate(1),
+            Transition::Quit => self.scenes.clear(),
+        }
+        !self.scenes.is_empty()
+    }
+
+    pub fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<bool> {
+        let top = self.scenes.len() - 1;
+        for scene in self.scenes[..top].iter_mut() {
+            scene.covered_update(ctx);
+        }
+        let transition = self.scenes[top].update(ctx, shared)?;
+        Ok(self.apply(transition))
+    }
+
+    pub fn input(&mut self, ctx: &mut Context, shared: &mut Shared, input: Input) -> bool {
+        let transition = match self.scenes.last_mut() {
+            Some(scene) => scene.input(ctx, shared, input),
+            None => Transition::None,
+        };
+        self.apply(transition)
+    }
+
+    // The window is closing, let every scene know.
+    pub fn quit(&mut self, ctx: &mut Context, shared: &mut Shared) {
+        for scene in self.scenes.iter_mut() {
+            scene.quit(ctx, shared);
+        }
+    }
+
+    // Draw the top scene, and any scenes below it that show through.
+    pub fn draw(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult {
+        let mut first = self.scenes.len() - 1;
+        while first > 0 && self.scenes[first].is_overlay() {
+            first -= 1;
+        }
+        for scene in self.scenes[first..].iter_mut() {
+            scene.draw(ctx, shared)?;
+        }
+        Ok(())
+    }
+}
+
+// Draw text centered across the screen with its top at y.
+pub fn draw_centered(ctx: &mut Context, text: &str, y: f32, size: f32, color: Color) -> GameResult {
+    let text = Text::new(TextFragment::new(text).scale(Scale::uniform(size)));
+    let (width, _) = graphics::drawable_size(ctx);
+    let x = (width - text.width(ctx) as f32) / 2.0;
+    graphics::draw(ctx, &text, (Point2::new(x, y), color))
+}
+
+// A list of choices, one of which is selected.
+#[derive(Debug, Default)]
+pub struct Menu {
+    pub selected: usize,
+    len: usize,
+}
+
+impl Menu {
+    pub fn new(len: usize) -> Menu {
+        Menu { selected: 0, len }
+    }
+
+    // Move the selection up or down.  Returns the selected item when
+    // it's picked.
+    pub fn command(&mut self, command: MenuCommand) -> Option<usize> {
+        match command {
+            MenuCommand::Up => self.selected = (self.selected + self.len - 1) % self.len,
+            MenuCommand::Down => self.selected = (self.selected + 1) % self.len,
+            MenuCommand::Select => return Some(self.selected),
+            _ => (),
+        }
+        None
+    }
+
+    // Draw a title and the items below it, down the middle of the
+    // screen, with the selected item highlighted.
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        layout: &Layout,
+        title: &str,
+        items: &[String],
+    ) -> GameResult {
+        let mut y = layout.playfield.y + layout.cell * 4.0;
+        draw_centered(ctx, title, y, layout.font_size() * 2.0, graphics::WHITE)?;
+        y += layout.cell * 4.0;
+        for (i, item) in items.iter().enumerate() {
+            let (text, color) = if i == self.selected {
+                (format!("> {} <", item), Color::new(1.0, 1.0, 0.0, 1.0))
+            } else {
+                (item.clone(), graphics::WHITE)
+            };
+            draw_centered(ctx, &text, y, layout.font_size(), color)?;
+            y += layout.line_height() * 1.5;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::spin::Spin;
+
+// Scoring systems.  A game hands its scoring system the facts about
+// each clear and each drop, and the system decides what they are worth
+// and when the level goes up.
+
+// Everything about a piece locking that can score.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Clear {
+    // The lines cleared, none for a spin that cleared nothing.
+    pub lines: u32,
+    pub spin: Option<Spin>,
+    // Pieces in a row before this one that cleared lines.
+    pub combo: u32,
+    // The last clear was also a difficult one, a tetris or a spin.
+    pub back_to_back: bool,
+    // The board is empty after the clear.
+    pub perfect: bool,
+}
+
+pub trait ScoringSystem {
+    fn name(&self) -> String;
+
+    // The points for clearing lines or spinning a piece at a level.
+    fn clear(&self, clear: &Clear, level: u32) -> u32;
+
+    // The points for pushing the piece down rows, or dropping it.
+    fn soft_drop(&self, _rows: u32) -> u32 {
+        0
+    }
+
+    fn hard_drop(&self, _rows: u32) -> u32 {
+        0
+    }
+
+    // The level once this many lines have been cleared in a game that
+    // started at the start level.
+    fn level(&self, start: u32, lines: u32) -> u32 {
+        start + lines / 10
+    }
+}
+
+// Which scoring system to use.  The custom one is read from a file.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Scoring {
+    Nes,
+    Guideline,
+    Custom,
+}
+
+impl Scoring {
+    pub fn name(self) -> String {
+        match self {
+            Scoring::Nes => "NES".to_string(),
+            Scoring::Guideline => "Guideline".to_string(),
+            Scoring::Custom => "Custom".to_string(),
+        }
+    }
+
+    // Make the scoring system, using the custom table if there is one.
+    pub fn system(self, custom: Option<&ScoreTable>) -> Box<dyn ScoringSystem> {
+        match (self, custom) {
+            (Scoring::Nes, _) => Box::new(Nes),
+            (Scoring::Custom, Some(table)) => Box::new(table.clone()),
+            _ => Box::new(ScoreTable::default()),
+        }
+    }
+}
+
+// The original NES scoring: 40, 100, 300 and 1200 for one to four
+// lines, times the level.  Soft drops are worth a point a row.
+#[derive(Debug)]
+pub struct Nes;
+
+impl ScoringSystem for Nes {
+    fn name(&self) -> String {
+        "NES".to_string()
+    }
+
+    fn clear(&self, clear: &Clear, level: u32) -> u32 {
+        let points = match clear.lines {
+            0 => 0,
+            1 => 40,
+            2 => 100,
+            3 => 300,
+            _ => 1200,
+        };
+        points * (level + 1)
+    }
+
+    fn soft_drop(&self, rows: u32) -> u32 {
+        rows
+    }
+
+    // Starting at a higher level, the first level up takes more lines:
+    // ten for every level started at, but no more than the larger of
+    // 100 and 50 fewer than that.  After that it is every ten lines.
+    fn level(&self, start: u32, lines: u32) -> u32 {
+        let first = (start * 10 + 10).min(100.max((start * 10).saturating_sub(50)));
+        if lines < first {
+            start
+        } else {
+            start + 1 + (lines - first) / 10
+        }
+    }
+}
+
+// Points for everything, from a table.  The default table is the
+// modern guideline scoring; a custom table can be read from a file,
+// and anything left out of the file is taken from the guideline.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ScoreTable {
+    pub name: String,
+    // For clearing none to four lines.  More than four scores as four.
+    pub lines: [u32; 5],
+    // For mini spins and spins, by the lines cleared with them.
+    pub mini_spin: [u32; 3],
+    pub spin: [u32; 4],
+    // For each piece in a row before this one that cleared lines.
+    pub combo: u32,
+    // Difficult clears back to back score this percentage.
+    pub back_to_back_percent: u32,
+    // For emptying the board, by the lines cleared.
+    pub perfect_clear: [u32; 5],
+    // The above are all times the level, starting from 1.
+    pub per_level: bool,
+    // For each row of a soft or hard drop.
+    pub soft_drop: u32,
+    pub hard_drop: u32,
+    pub lines_per_level: u32,
+}
+
+impl Default for ScoreTable {
+    fn default() -> ScoreTable {
+        ScoreTable {
+            name: "Guideline".to_string(),
+            lines: [0, 100, 300, 500, 800],
+            mini_spin: [100, 200, 400],
+            spin: [400, 800, 1200, 1600],
+            combo: 50,
+            back_to_back_percent: 150,
+            perfect_clear: [0, 800, 1200, 1800, 2000],
+            per_level: true,
+            soft_drop: 1,
+            hard_drop: 2,
+            lines_per_level: 10,
+        }
+    }
+}
+
+impl ScoreTable {
+    // Read a custom table.  Returns none, after saying why, if the
+    // file is there but can't be used.
+    pub fn load(path: &Path) -> Option<ScoreTable> {
+        let text = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&text) {
+            Ok(table) => Some(table),
+            Err(e) => {
+                println!("Unable to read {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+impl ScoringSystem for ScoreTable {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn clear(&self, clear: &Clear, level: u32) -> u32 {
+        let lines = clear.lines as usize;
+        let mut points = match clear.spin {
+            Some(Spin::Mini) => self.mini_spin[lines.min(2)],
+            Some(Spin::Full) => self.spin[lines.min(3)],
+            None => self.lines[lines.min(4)],
+        };
+        let difficult = clear.lines >= 4 || (clear.spin.is_some() && clear.lines > 0);
+        if difficult && clear.back_to_back {
+            points = points * self.back_to_back_percent / 100;
+        }
+        if clear.lines > 0 {
+            points += self.combo * clear.combo;
+        }
+        if clear.perfect {
+            points += self.perfect_clear[lines.min(4)];
+        }
+        if self.per_level {
+            points *= level + 1;
+        }
+        points
+    }
+
+    fn soft_drop(&self, rows: u32) -> u32 {
+        self.soft_drop * rows
+    }
+
+    fn hard_drop(&self, rows: u32) -> u32 {
+        self.hard_drop * rows
+    }
+
+    fn level(&self, start: u32, lines: u32) -> u32 {
+        start + lines / self.lines_per_level.max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(lines: u32, spin: Option<Spin>) -> Clear {
+        Clear {
+            lines,
+            spin,
+            combo: 0,
+            back_to_back: false,
+            perfect: false,
+        }
+    }
+
+    #[test]
+    fn nes_levels() {
+        // From 0 the first level up is at 10 lines
+        assert_eq!(Nes.level(0, 9), 0);
+        assert_eq!(Nes.level(0, 10), 1);
+        assert_eq!(Nes.level(0, 25), 2);
+        // From 5 it is at 60
+        assert_eq!(Nes.level(5, 59), 5);
+        assert_eq!(Nes.level(5, 60), 6);
+        assert_eq!(Nes.level(5, 70), 7);
+        // From 15 it is at 100, not 160
+        assert_eq!(Nes.level(15, 99), 15);
+        assert_eq!(Nes.level(15, 100), 16);
+        // From 19 it is at 140, 50 fewer than ten a level
+        assert_eq!(Nes.level(19, 139), 19);
+        assert_eq!(Nes.level(19, 140), 20);
+        assert_eq!(Nes.level(19, 150), 21);
+    }
+
+    #[test]
+    fn nes_clears() {
+        assert_eq!(Nes.clear(&clear(1, None), 0), 40);
+        assert_eq!(Nes.clear(&clear(4, None), 0), 1200);
+        assert_eq!(Nes.clear(&clear(4, None), 9), 12_000);
+        assert_eq!(Nes.clear(&clear(0, Some(Spin::Full)), 3), 0);
+    }
+
+    #[test]
+    fn table_clears_and_spins() {
+        let table = ScoreTable::default();
+        assert_eq!(table.clear(&clear(1, None), 0), 100);
+        assert_eq!(table.clear(&clear(4, None), 2), 2400);
+        assert_eq!(table.clear(&clear(0, Some(Spin::Full)), 0), 400);
+        assert_eq!(table.clear(&clear(2, Some(Spin::Full)), 0), 1200);
+        assert_eq!(table.clear(&clear(0, Some(Spin::Mini)), 0), 100);
+        assert_eq!(table.clear(&clear(1, Some(Spin::Mini)), 1), 400);
+    }
+
+    #[test]
+    fn table_back_to_back() {
+        let table = ScoreTable::default();
+        let b2b = |lines, spin| Clear {
+            back_to_back: true,
+            ..clear(lines, spin)
+        };
+        assert_eq!(table.clear(&b2b(4, None), 0), 1200);
+        assert_eq!(table.clear(&b2b(2, Some(Spin::Full)), 0), 1800);
+        // Only difficult clears earn it
+        assert_eq!(table.clear(&b2b(1, None), 0), 100);
+        assert_eq!(table.clear(&b2b(0, Some(Spin::Full)), 0), 400);
+    }
+
+    #[test]
+    fn table_combo_and_perfect_clear() {
+        let table = ScoreTable::default();
+        let combo = Clear {
+            combo: 3,
+            ..clear(1, None)
+        };
+        // (100 + 3 × 50) × level 1
+        assert_eq!(table.clear(&combo, 1), 500);
+        // A spin that clears nothing doesn't carry a combo
+        let spin = Clear {
+            combo: 3,
+            ..clear(0, Some(Spin::Full))
+        };
+        assert_eq!(table.clear(&spin, 0), 400);
+        let perfect = Clear {
+            perfect: true,
+            ..clear(4, None)
+        };
+        assert_eq!(table.clear(&perfect, 0), 2800);
+        assert_eq!(table.level(0, 29), 2);
+    }
+}
@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{validate_move, Board, Piece, TileType};
+
+// Wall kicks and spin detection.
+//
+// Our rotations match the Super Rotation System, so a turn that doesn't
+// fit where it is tries the SRS kicks, the same places in the same
+// order as the guideline games.  Offsets are in board squares with y
+// going down the board.
+
+// The kicks for turning clockwise out of each rotation, for pieces
+// that turn in a 3x3 box.  The long pieces like the I have their own,
+// and pieces in a smaller box, like the O, never need to kick.
+const KICKS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+];
+
+const I_KICKS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+];
+
+// The last kick, the one that moves the piece two rows, turns a mini
+// T-spin into a full one.
+const UPGRADE_KICK: usize = 4;
+
+// Which kicks a piece uses.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Kicks {
+    None,
+    Standard,
+    Long,
+}
+
+// How a piece was spun into place.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Spin {
+    Mini,
+    Full,
+}
+
+// The places to try, in order, when turning the piece clockwise.
+pub fn kicks(piece: Piece, table: Kicks) -> &'static [(i32, i32)] {
+    let rotation = (piece.rotation % 4) as usize;
+    match table {
+        Kicks::None => &KICKS[0][..1],
+        Kicks::Long => &I_KICKS[rotation],
+        Kicks::Standard => &KICKS[rotation],
+    }
+}
+
+// Is this square taken, or off the board?
+fn occupied(board: &Board, x: usize, y: usize) -> bool {
+    if x >= board.width() || y >= board.height() {
+        return true;
+    }
+    let tile = board.get(x, y);
+    tile.is_filled() || tile == TileType::Border
+}
+
+// Was the piece spun into where it has just landed?  The last thing
+// done with it must have been a turn, using the kick given.
+//
+// A T, the piece given as t_spin, uses the three corner rule: three of the four squares diagonal
+// to its middle must be taken.  With both corners on the side it
+// points to taken it's a full T-spin, otherwise a mini, unless it got
+// there with the last kick.  With all_spin, any other piece that
+// can't move left, right or up counts as a mini spin.
+pub fn detect(
+    board: &Board,
+    piece: Piece,
+    kick: Option<usize>,
+    t_spin: bool,
+    all_spin: bool,
+) -> Option<Spin> {
+    let kick = kick?;
+    if !t_spin {
+        return if all_spin && immobile(board, piece) {
+            Some(Spin::Mini)
+        } else {
+            None
+        };
+    }
+
+    // The corners of the T's 3x3 box, and the two in front for each
+    // rotation: pointing up, right, down and left.
+    let (x, y) = (piece.x, piece.y);
+    let corners = [(x, y), (x + 2, y), (x + 2, y + 2), (x, y + 2)];
+    let front = match piece.rotation % 4 {
+        0 => [0, 1],
+        1 => [1, 2],
+        2 => [2, 3],
+        _ => [3, 0],
+    };
+    let taken = corners
+        .iter()
+        .filter(|(cx, cy)| occupied(board, *cx, *cy))
+        .count();
+    if taken < 3 {
+        return None;
+    }
+    let front_taken = front
+        .iter()
+        .all(|&i| occupied(board, corners[i].0, corners[i].1));
+    if front_taken || kick == UPGRADE_KICK {
+        Some(Spin::Full)
+    } else {
+        Some(Spin::Mini)
+    }
+}
+
+// The piece can't move in any direction but down.
+fn immobile(board: &Board, piece: Piece) -> bool {
+    let mut moves = vec![Piece {
+        x: piece.x + 1,
+        ..piece
+    }];
+    if piece.x > 0 {
+        moves.push(Piece {
+            x: piece.x - 1,
+            ..piece
+        });
+    }
+    if piece.y > 0 {
+        moves.push(Piece {
+            y: piece.y - 1,
+            ..piece
+        });
+    }
+    moves.iter().all(|&to| !validate_move(board, to))
+}
+
+// What to call a spin, like "T-Spin Double" or "Mini T-Spin".
+pub fn spin_name(piece: &str, spin: Spin, lines: u32) -> String {
+    let mut name = match spin {
+        Spin::Mini => format!("Mini {}-Spin", piece),
+        Spin::Full => format!("{}-Spin", piece),
+    };
+    match lines {
+        0 => (),
+        1 => name.push_str(" Single"),
+        2 => name.push_str(" Double"),
+        _ => name.push_str(" Triple"),
+    }
+    name
+}
@@ -0,0 +1,167 @@
+use std::mem;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::MAX_UNDO;
+use crate::event::{ClearKind, GameEvent, Subscriber};
+use crate::spin::Spin;
+
+// Running totals for the current game, kept up to date from the game
+// events.  An undo takes them back along with the game.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Stats {
+    // How many of each piece have come in, in the order of the piece
+    // set.
+    pub pieces: Vec<u32>,
+    pub singles: u32,
+    pub doubles: u32,
+    pub triples: u32,
+    pub tetrises: u32,
+    // T-spins by the lines they cleared, from none to three, then the
+    // mini T-spins and spins with other pieces.
+    pub t_spins: [u32; 4],
+    pub mini_t_spins: u32,
+    pub other_spins: u32,
+    // The longest combo and run of back to back clears.
+    pub max_combo: u32,
+    pub max_back_to_back: u32,
+    pub perfect_clears: u32,
+    pub hard_drops: u32,
+    pub holds: u32,
+    // Every move and rotation, a rough measure of how efficiently the
+    // pieces were placed.
+    pub inputs: u32,
+    // The totals as they were when each of the last few pieces came
+    // in, the same ones the game keeps for undo, and whether the piece
+    // in play came in from a hold, which the game doesn't keep.
+    #[serde(skip)]
+    history: Vec<Stats>,
+    #[serde(skip)]
+    held: bool,
+}
+
+impl Stats {
+    pub fn total_pieces(&self) -> u32 {
+        self.pieces.iter().sum()
+    }
+
+    pub fn total_t_spins(&self) -> u32 {
+        self.t_spins.iter().sum()
+    }
+
+    fn remember(&mut self) {
+        let mut history = mem::take(&mut self.history);
+        if history.len() >= MAX_UNDO {
+            history.remove(0);
+        }
+        history.push(self.clone());
+        self.history = history;
+    }
+
+    fn undo(&mut self) {
+        if self.history.len() < 2 {
+            return;
+        }
+        let mut history = mem::take(&mut self.history);
+        history.pop();
+        *self = history[history.len() - 1].clone();
+        self.history = history;
+    }
+}
+
+impl Subscriber for Stats {
+    fn notify(&mut self, event: &GameEvent, _now: Duration) {
+        match event {
+            GameEvent::PieceSpawned { piece, .. } => {
+                let i = piece.tet_type.0 as usize;
+                if self.pieces.len() <= i {
+                    self.pieces.resize(i + 1, 0);
+                }
+                self.pieces[i] += 1;
+                if !self.held {
+                    self.remember();
+                }
+            }
+            GameEvent::Locked { .. } => self.held = false,
+            GameEvent::Undone { .. } => self.undo(),
+            GameEvent::Moved { .. } | GameEvent::Rotated { .. } => self.inputs += 1,
+            GameEvent::HardDrop { .. } => self.hard_drops += 1,
+            GameEvent::Hold { .. } => {
+                self.holds += 1;
+                self.held = true;
+            }
+            GameEvent::Spun {
+                t_spin,
+                spin,
+                lines,
+                ..
+            } => match (t_spin, spin) {
+                (true, Spin::Full) => self.t_spins[(*lines as usize).min(3)] += 1,
+                (true, Spin::Mini) => self.mini_t_spins += 1,
+                _ => self.other_spins += 1,
+            },
+            GameEvent::PerfectClear { .. } => self.perfect_clears += 1,
+            GameEvent::Combo { count } => self.max_combo = self.max_combo.max(*count),
+            GameEvent::BackToBack { count } => {
+                self.max_back_to_back = self.max_back_to_back.max(*count)
+            }
+            GameEvent::LinesCleared { kind, .. } => match kind {
+                ClearKind::Single => self.singles += 1,
+                ClearKind::Double => self.doubles += 1,
+                ClearKind::Triple => self.triples += 1,
+                ClearKind::Tetris => self.tetrises += 1,
+            },
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Action, Game, Rules};
+    use crate::pieces::PieceSet;
+    use crate::scoring::ScoreTable;
+
+    // Apply the actions to the game, passing its events to the stats.
+    fn play(game: &mut Game, stats: &mut Stats, actions: &[Action]) {
+        for &action in actions {
+            game.apply(action);
+            for event in game.take_events() {
+                stats.notify(&event, Duration::from_secs(0));
+            }
+        }
+    }
+
+    #[test]
+    fn undo_takes_back_the_counts() {
+        let rules = Rules {
+            undo: true,
+            ..Rules::default()
+        };
+        let scoring = Box::new(ScoreTable::default());
+        let mut game = Game::new(1, rules, scoring, PieceSet::default());
+        let mut stats = Stats::default();
+        for event in game.take_events() {
+            stats.notify(&event, Duration::from_secs(0));
+        }
+        play(&mut game, &mut stats, &[Action::HardDrop, Action::Hold]);
+        play(&mut game, &mut stats, &[Action::HardDrop]);
+        // The first piece, the second, the one the hold brought in and
+        // the one after it
+        assert_eq!(stats.total_pieces(), 4);
+        assert_eq!(stats.hard_drops, 2);
+
+        play(&mut game, &mut stats, &[Action::Undo]);
+        assert_eq!(stats.total_pieces(), 2);
+        assert_eq!(stats.hard_drops, 1);
+        assert_eq!(stats.holds, 0);
+        play(&mut game, &mut stats, &[Action::Undo]);
+        assert_eq!(stats.total_pieces(), 1);
+        assert_eq!(stats.hard_drops, 0);
+        // There is nothing before the first piece
+        play(&mut game, &mut stats, &[Action::Undo]);
+        assert_eq!(stats.total_pieces(), 1);
+    }
+}
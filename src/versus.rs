@@ -0,0 +1,95 @@
+use std::mem;
+use std::time::Duration;
+
+use crate::bot::Bot;
+use crate::engine::{BoardState, Game};
+use crate::event::{GameEvent, Subscriber};
+use crate::spin::Spin;
+
+// A game against the bot.  The bot plays its own game alongside the
+// player's, with the same pieces in the same order, and the lines each
+// side clears send rows of garbage to the other.  Whoever tops out
+// first loses.
+
+// Rows of garbage sent for clearing 0 to 4 lines at once.
+const LINES_SENT: [u32; 5] = [0, 0, 1, 2, 4];
+
+// Rows sent for emptying the board.
+const PERFECT_CLEAR_SENT: u32 = 10;
+
+// The rows of garbage a game event sends to the other side, as in the
+// guideline games: a tetris sends four, a T-spin two for each line it
+// clears in place of the usual, and back to back clears one more.
+pub fn garbage_sent(event: &GameEvent) -> u32 {
+    match event {
+        GameEvent::LinesCleared { count, .. } => LINES_SENT[(*count as usize).min(4)],
+        GameEvent::Spun {
+            t_spin: true,
+            spin: Spin::Full,
+            lines,
+            ..
+        } if *lines > 0 => 2 * lines - LINES_SENT[(*lines as usize).min(4)],
+        GameEvent::BackToBack { .. } => 1,
+        GameEvent::PerfectClear { .. } => PERFECT_CLEAR_SENT,
+        _ => 0,
+    }
+}
+
+// Rows sent by one side first cancel any on their way to it, and the
+// rest go on to the other side.
+fn send(rows: u32, incoming: &mut u32, outgoing: &mut u32) {
+    let cancelled = rows.min(*incoming);
+    *incoming -= cancelled;
+    *outgoing += rows - cancelled;
+}
+
+pub struct Versus {
+    // The bot's game, and the bot playing it.
+    pub game: Game,
+    bot: Bot,
+    // Rows of garbage on their way to each side.  They come in under
+    // that side's next piece.
+    pub to_player: u32,
+    pub to_bot: u32,
+}
+
+impl Versus {
+    pub fn new(game: Game, bot: Bot) -> Versus {
+        Versus {
+            game,
+            bot,
+            to_player: 0,
+            to_bot: 0,
+        }
+    }
+
+    // The bot has lost.
+    pub fn is_over(&self) -> bool {
+        self.game.board_state == BoardState::Over
+    }
+
+    // Let the bot play for the time since the last call, and send and
+    // take garbage as its game goes.
+    pub fn update(&mut self, dt: Duration, now: Duration) {
+        self.game.advance(dt);
+        self.bot.play(&mut self.game, dt);
+        self.game.fall(dt);
+        for event in self.game.take_events() {
+            send(garbage_sent(&event), &mut self.to_bot, &mut self.to_player);
+            if let GameEvent::PieceSpawned { .. } = event {
+                self.game.add_garbage(mem::take(&mut self.to_bot));
+            }
+            self.bot.notify(&event, now);
+        }
+    }
+
+    // An event from the player's game.  Returns the rows of garbage
+    // to add to the player's board now.
+    pub fn player_event(&mut self, event: &GameEvent) -> u32 {
+        send(garbage_sent(event), &mut self.to_player, &mut self.to_bot);
+        match event {
+            GameEvent::PieceSpawned { .. } => mem::take(&mut self.to_player),
+            _ => 0,
+        }
+    }
+}